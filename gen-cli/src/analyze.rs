@@ -0,0 +1,99 @@
+use gen_core::prelude::{Layout, LayoutFile, Optimizer, TrigramTypes};
+use getargs::Arg;
+use serde_json::json;
+
+use crate::{load, parse_format, print_json, watch, CliError, CliOptions, Format};
+
+const USAGE: &str = "\
+usage: gen-cli analyze [options] <layout>
+
+Scores a layout and shows how often each type of trigram and each finger is
+used. The layout is either a .dof file or a text file with a row of keys per
+line, like what gen-cli prints.
+
+options:
+    -c, --corpus <corpus.json>    corpus to analyze with
+    -w, --weights <weights.toml>  weights of trigram types and fingers
+    -k, --keyboard <file>         .dof or keyboard-layout-editor .json file to take
+                                  the keyboard and fingering from, instead of the layout
+    -f, --format <text|json>      how to print the results, text by default
+        --watch                   keep the corpus loaded and analyze the layout
+                                  again whenever it, the weights, keyboard or
+                                  oxeygen.toml change";
+
+pub(crate) fn run<'a, I: Iterator<Item = &'a str>>(
+    opts: &mut CliOptions<'a, I>,
+) -> Result<(), CliError> {
+    let mut flags = load::ProjectFlags::default();
+    let mut format = Format::Text;
+    let mut watch = false;
+    let mut path = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('c') | Arg::Long("corpus") => flags.corpus = Some(opts.value()?),
+            Arg::Short('w') | Arg::Long("weights") => flags.weights = Some(opts.value()?),
+            Arg::Short('k') | Arg::Long("keyboard") => flags.keyboard = Some(opts.value()?),
+            Arg::Short('f') | Arg::Long("format") => format = parse_format(opts.value()?)?,
+            Arg::Long("watch") => watch = true,
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if path.is_none() => path = Some(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let path = path.ok_or(CliError::MissingArgument("<layout>"))?;
+
+    if watch {
+        return watch::analyze(&flags, path, format);
+    }
+
+    let file = LayoutFile::load(path)?;
+    let project = load::project(&flags)?;
+    let keyboard = project.keyboard()?.unwrap_or_else(|| file.keyboard());
+
+    let types = TrigramTypes::with_defaults(keyboard);
+    let optimizer = load::optimizer(&project, &types)?;
+
+    print(&optimizer, &file, format)
+}
+
+/// Analyzes the layout of a file and prints it in `format`.
+pub(crate) fn print(
+    optimizer: &Optimizer,
+    file: &LayoutFile,
+    format: Format,
+) -> Result<(), CliError> {
+    let layout = file.layout(optimizer)?;
+
+    match format {
+        Format::Text => print_analysis(optimizer, file.name(), &layout),
+        Format::Json => print_json(
+            "analyze",
+            json!({ "layout": optimizer.analyze(file.name(), &layout) }),
+        ),
+    }
+
+    Ok(())
+}
+
+/// Prints a layout with its score, trigram types and finger usage.
+pub(crate) fn print_analysis(optimizer: &Optimizer, name: &str, layout: &Layout) {
+    let analysis = optimizer.analyze(name, layout);
+
+    println!("{name}\n{}\n", optimizer.layout_to_str(layout));
+    println!("score: {:.3}", analysis.score);
+
+    println!("\ntrigram types:");
+    for f in &analysis.trigram_types {
+        println!("    {:<14}{:>7.3}%", f.name, f.percentage);
+    }
+
+    println!("\nfinger usage:");
+    for f in &analysis.finger_usage {
+        println!("    {:<14}{:>7.3}%", f.name, f.percentage);
+    }
+}
@@ -1,3 +1,6 @@
-fn main() -> Result<(), gen_cli::CliError> {
-    gen_cli::cli()
+fn main() {
+    if let Err(e) = gen_cli::cli() {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,118 @@
+use gen_core::prelude::{Layout, LayoutFile, Optimizer, TrigramTypes};
+use getargs::Arg;
+use serde_json::json;
+
+use crate::{load, parse_format, print_json, CliError, CliOptions, Format};
+
+const USAGE: &str = "\
+usage: gen-cli compare [options] <layout> <layout>
+
+Scores two layouts with the same corpus and weights, and shows their statistics
+side by side. Layouts are .dof files or text files like for analyze.
+
+options:
+    -c, --corpus <corpus.json>    corpus to analyze with
+    -w, --weights <weights.toml>  weights of trigram types and fingers
+    -k, --keyboard <file>         .dof or keyboard-layout-editor .json file to take
+                                  the keyboard and fingering from, instead of the
+                                  first layout
+    -f, --format <text|json>      how to print the results, text by default";
+
+pub(crate) fn run<'a, I: Iterator<Item = &'a str>>(
+    opts: &mut CliOptions<'a, I>,
+) -> Result<(), CliError> {
+    let mut flags = load::ProjectFlags::default();
+    let mut format = Format::Text;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('c') | Arg::Long("corpus") => flags.corpus = Some(opts.value()?),
+            Arg::Short('w') | Arg::Long("weights") => flags.weights = Some(opts.value()?),
+            Arg::Short('k') | Arg::Long("keyboard") => flags.keyboard = Some(opts.value()?),
+            Arg::Short('f') | Arg::Long("format") => format = parse_format(opts.value()?)?,
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if paths.len() < 2 => paths.push(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let [a, b] = paths[..] else {
+        return Err(CliError::MissingArgument("<layout> <layout>"));
+    };
+
+    let a = LayoutFile::load(a)?;
+    let b = LayoutFile::load(b)?;
+    let project = load::project(&flags)?;
+    let keyboard = project.keyboard()?.unwrap_or_else(|| a.keyboard());
+
+    let types = TrigramTypes::with_defaults(keyboard);
+    let optimizer = load::optimizer(&project, &types)?;
+    let layouts = [
+        (a.name(), a.layout(&optimizer)?),
+        (b.name(), b.layout(&optimizer)?),
+    ];
+
+    match format {
+        Format::Text => print_comparison(&optimizer, &layouts),
+        Format::Json => {
+            let layouts = layouts
+                .iter()
+                .map(|(name, layout)| optimizer.analyze(name, layout))
+                .collect::<Vec<_>>();
+
+            print_json("compare", json!({ "layouts": layouts }));
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints two layouts next to each other, followed by their scores, trigram types and finger
+/// usage in two columns.
+pub(crate) fn print_comparison(optimizer: &Optimizer, [(a, l1), (b, l2)]: &[(&str, Layout); 2]) {
+    let s1 = optimizer.layout_to_str(l1);
+    let s2 = optimizer.layout_to_str(l2);
+    let width = s1
+        .lines()
+        .map(|l| l.chars().count())
+        .chain([a.chars().count()])
+        .max()
+        .unwrap_or(0);
+
+    println!("{a:<width$}    {b}");
+    for (r1, r2) in s1.lines().zip(s2.lines()) {
+        println!("{r1:<width$}    {r2}");
+    }
+
+    let a1 = optimizer.analyze(a, l1);
+    let a2 = optimizer.analyze(b, l2);
+
+    println!("\n{:<14}{:>8.3} {:>8.3}", "score", a1.score, a2.score);
+
+    let missing = a2
+        .trigram_types
+        .iter()
+        .filter(|f| a1.trigram_types.iter().all(|g| g.name != f.name));
+
+    println!("\ntrigram types:");
+    for f in a1.trigram_types.iter().chain(missing) {
+        println!(
+            "    {:<14}{:>7.3}% {:>7.3}%",
+            f.name,
+            a1.trigram_type(&f.name),
+            a2.trigram_type(&f.name)
+        );
+    }
+
+    println!("\nfinger usage:");
+    for (f1, f2) in a1.finger_usage.iter().zip(&a2.finger_usage) {
+        println!(
+            "    {:<14}{:>7.3}% {:>7.3}%",
+            f1.name, f1.percentage, f2.percentage
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use std::path::{Path, PathBuf};
+
+use gen_core::prelude::{CorpusProgress, CorpusStats, Data, NgramFrequency};
+use getargs::Arg;
+use serde_json::json;
+
+use crate::{load, parse_format, parse_value, print_json, CliError, CliOptions, Format};
+
+const USAGE: &str = "\
+usage: gen-cli corpus <command> [options]
+
+commands:
+    build    count the trigrams of a text file or folder
+    blend    blend several corpora into a new one
+    inspect  show how well a refiner fits a text, or what a corpus contains
+    prune    drop rare trigrams so optimizing with a corpus is faster
+    refine   clean up a corpus that was already counted, without its text";
+
+const BUILD_USAGE: &str = "\
+usage: gen-cli corpus build --name <name> [options] <path>
+
+Counts the trigrams of a file, or of every file in a folder, after cleaning the
+text with a refiner.
+
+options:
+    -n, --name <name>         name of the corpus
+    -r, --refiner <refiner>   refiner preset, or a config as .toml or .json,
+                              english by default
+    -p, --preset <preset>     same as --refiner
+    -o, --out <folder>        folder to save it in, ./data by default";
+
+const BLEND_USAGE: &str = "\
+usage: gen-cli corpus blend --name <name> [--out <folder>] <corpus.json:weight>...
+
+Blends corpora so each one makes up its share of the result, for example
+`english.json:60 bokmal.json:25 rust.json:15`. The recipe is stored in the
+metadata of the new corpus.
+
+options:
+    -n, --name <name>       name of the blended corpus
+    -o, --out <folder>      folder to save it in, ./data by default";
+
+const INSPECT_USAGE: &str = "\
+usage: gen-cli corpus inspect [options] <path>
+
+Refines a text file or folder and reports which characters the refiner has no
+translation for and how many trigrams were discarded because of them. For a
+corpus .json file, only its most common n-grams are shown.
+
+options:
+    -r, --refiner <refiner>   refiner preset, or a config as .toml or .json,
+                              english by default
+    -p, --preset <preset>     same as --refiner
+    -t, --top <n>             amount of characters and n-grams to show, 10 by default
+    -f, --format <text|json>  how to print the results, text by default";
+
+const PRUNE_USAGE: &str = "\
+usage: gen-cli corpus prune [options] <corpus.json>
+
+Makes a corpus smaller so the optimizer is faster to set up, at the cost of
+some accuracy. Rare characters are folded together first, then trigrams are
+removed. Percentages of what's left are scaled back up to add up to 100%.
+
+options:
+    -c, --chars <n>         keep the n most common characters, fold the rest into one
+    -m, --mass <percent>    keep the most common trigrams that make up this much of the corpus
+    -k, --top <k>           keep the k most common trigrams
+        --min <percent>     drop trigrams that make up less than this much of the corpus
+    -n, --name <name>       name of the pruned corpus, the original name by default
+    -o, --out <folder>      folder to save it in, ./data by default";
+
+const REFINE_USAGE: &str = "\
+usage: gen-cli corpus refine [options] <corpus.json>
+
+Applies a refiner to the trigrams of a corpus, for when the text it was counted
+from isn't available. This is an approximation: each trigram is refined in the
+context of the trigrams around it, which is exact for most rules but not for
+ones that match across more than four characters.
+
+options:
+    -r, --refiner <refiner>   refiner preset, or a config as .toml or .json,
+                              english by default
+    -p, --preset <preset>     same as --refiner
+    -n, --name <name>         name of the refined corpus, the original name by default
+    -o, --out <folder>        folder to save it in, ./data by default";
+
+pub(crate) fn run<'a, I: Iterator<Item = &'a str>>(
+    opts: &mut CliOptions<'a, I>,
+) -> Result<(), CliError> {
+    match opts.next_arg()? {
+        Some(Arg::Positional("build")) => build(opts),
+        Some(Arg::Positional("blend")) => blend(opts),
+        Some(Arg::Positional("inspect")) => inspect(opts),
+        Some(Arg::Positional("prune")) => prune(opts),
+        Some(Arg::Positional("refine")) => refine(opts),
+        Some(Arg::Positional(command)) => {
+            Err(CliError::UnknownCommand(format!("corpus {command}")))
+        }
+        _ => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+fn build<'a, I: Iterator<Item = &'a str>>(opts: &mut CliOptions<'a, I>) -> Result<(), CliError> {
+    let mut name = None;
+    let mut out = PathBuf::from("./data");
+    let mut flags = load::ProjectFlags::default();
+    let mut path = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('n') | Arg::Long("name") => name = Some(opts.value()?),
+            Arg::Short('o') | Arg::Long("out") => out = opts.value()?.into(),
+            Arg::Short('p') | Arg::Long("preset") | Arg::Short('r') | Arg::Long("refiner") => {
+                flags.refiner = Some(opts.value()?);
+            }
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{BUILD_USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if path.is_none() => path = Some(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let name = name.ok_or(CliError::MissingArgument("--name"))?;
+    let path = path.ok_or(CliError::MissingArgument("<path>"))?;
+    let config = load::project(&flags)?.refiner()?;
+
+    let refiner = config.build();
+    let (data, stats) = Data::from_path_with_progress(path, name, &refiner, print_progress)?;
+    eprint!("\r{:60}\r", "");
+
+    print_stats(&stats);
+    println!("unique:      {}", data.inner().len());
+    print_unmapped(&stats, 5);
+
+    data.save(&out)?;
+    let saved = out.join(name).with_extension("json");
+    println!("\nsaved {}", saved.display());
+
+    Ok(())
+}
+
+fn print_progress(progress: CorpusProgress) {
+    const MB: f64 = 1024.0 * 1024.0;
+
+    let percentage = match progress.total_bytes {
+        0 => 100.0,
+        total => progress.bytes as f64 / total as f64 * 100.0,
+    };
+
+    eprint!(
+        "\rfile {}/{}, {:.1}/{:.1} MB ({percentage:.0}%)",
+        progress.files,
+        progress.total_files,
+        progress.bytes as f64 / MB,
+        progress.total_bytes as f64 / MB,
+    );
+}
+
+fn print_stats(stats: &CorpusStats) {
+    if stats.files > 1 || !stats.skipped_files.is_empty() {
+        println!("files:       {}", stats.files);
+    }
+    for file in &stats.skipped_files {
+        println!("skipped:     {file}");
+    }
+    println!("characters:  {}", stats.chars);
+    println!(
+        "unmapped:    {} ({:.3}%)",
+        stats.unmapped_chars(),
+        stats.unmapped_fraction() * 100.0
+    );
+    println!("trigrams:    {}", stats.trigrams);
+    println!(
+        "discarded:   {} ({:.3}%)",
+        stats.discarded_trigrams,
+        stats.discarded_fraction() * 100.0
+    );
+}
+
+fn print_unmapped(stats: &CorpusStats, top: usize) {
+    let unmapped = stats.top_unmapped(top);
+    if !unmapped.is_empty() {
+        println!("\nmost common unmapped characters:");
+        for (c, count) in unmapped {
+            println!("    {:<8} {count}", format!("{c:?}"));
+        }
+    }
+}
+
+fn blend<'a, I: Iterator<Item = &'a str>>(opts: &mut CliOptions<'a, I>) -> Result<(), CliError> {
+    let mut name = None;
+    let mut out = PathBuf::from("./data");
+    let mut sources = Vec::new();
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('n') | Arg::Long("name") => name = Some(opts.value()?),
+            Arg::Short('o') | Arg::Long("out") => out = opts.value()?.into(),
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{BLEND_USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(source) => sources.push(parse_source(source)?),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let name = name.ok_or(CliError::MissingArgument("--name"))?;
+
+    if sources.is_empty() {
+        return Err(CliError::MissingArgument("<corpus.json:weight>"));
+    }
+
+    let loaded = sources
+        .iter()
+        .map(|(path, weight)| Ok((Data::load(path)?, *weight)))
+        .collect::<Result<Vec<_>, CliError>>()?;
+
+    let parts = loaded.iter().map(|(d, w)| (d, *w)).collect::<Vec<_>>();
+    let blended = Data::blend(&parts, name)?;
+
+    for source in blended.meta().blend.iter().flatten() {
+        println!("{:>6.2}%  {}", source.weight * 100.0, source.name);
+    }
+
+    blended.save(&out)?;
+    println!("saved {}", out.join(name).with_extension("json").display());
+
+    Ok(())
+}
+
+fn inspect<'a, I: Iterator<Item = &'a str>>(opts: &mut CliOptions<'a, I>) -> Result<(), CliError> {
+    let mut top = 10;
+    let mut flags = load::ProjectFlags::default();
+    let mut format = Format::Text;
+    let mut path = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('t') | Arg::Long("top") => top = parse_value("--top", opts.value()?)?,
+            Arg::Short('f') | Arg::Long("format") => format = parse_format(opts.value()?)?,
+            Arg::Short('p') | Arg::Long("preset") | Arg::Short('r') | Arg::Long("refiner") => {
+                flags.refiner = Some(opts.value()?);
+            }
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{INSPECT_USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if path.is_none() => path = Some(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let path = Path::new(path.ok_or(CliError::MissingArgument("<path>"))?);
+
+    let (data, stats) = if path.extension().is_some_and(|e| e == "json") {
+        (Data::load(path)?, None)
+    } else {
+        let config = load::project(&flags)?.refiner()?;
+        let (data, stats) = Data::from_path_with_stats(path, "inspect", &config.build())?;
+
+        (data, Some(stats))
+    };
+
+    let characters = top_ngrams::<1>(&data, top);
+    let bigrams = top_ngrams::<2>(&data, top);
+    let trigrams = top_ngrams::<3>(&data, top);
+
+    match format {
+        Format::Text => {
+            if let Some(stats) = &stats {
+                print_stats(stats);
+                print_unmapped(stats, top);
+            }
+
+            print_ngrams("characters", &characters);
+            print_ngrams("bigrams", &bigrams);
+            print_ngrams("trigrams", &trigrams);
+        }
+        Format::Json => print_json(
+            "corpus inspect",
+            json!({
+                "stats": stats,
+                "characters": characters,
+                "bigrams": bigrams,
+                "trigrams": trigrams,
+            }),
+        ),
+    }
+
+    Ok(())
+}
+
+fn top_ngrams<const N: usize>(data: &Data, top: usize) -> Vec<NgramFrequency> {
+    data.top_ngrams::<N>(top)
+        .into_iter()
+        .map(|(ngram, count)| NgramFrequency::new(ngram, count, data.total()))
+        .collect()
+}
+
+fn print_ngrams(title: &str, ngrams: &[NgramFrequency]) {
+    println!("\nmost common {title}:");
+
+    for ngram in ngrams {
+        let name = format!("{:?}", ngram.ngram);
+        println!("    {name:<8} {:>7.3}%", ngram.percentage);
+    }
+}
+
+fn prune<'a, I: Iterator<Item = &'a str>>(opts: &mut CliOptions<'a, I>) -> Result<(), CliError> {
+    let mut name = None;
+    let mut out = PathBuf::from("./data");
+    let mut chars = None;
+    let mut mass = None;
+    let mut top = None;
+    let mut min = None;
+    let mut path = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('n') | Arg::Long("name") => name = Some(opts.value()?),
+            Arg::Short('o') | Arg::Long("out") => out = opts.value()?.into(),
+            Arg::Short('c') | Arg::Long("chars") => {
+                chars = Some(parse_value("--chars", opts.value()?)?)
+            }
+            Arg::Short('m') | Arg::Long("mass") => {
+                mass = Some(parse_value("--mass", opts.value()?)?)
+            }
+            Arg::Short('k') | Arg::Long("top") => top = Some(parse_value("--top", opts.value()?)?),
+            Arg::Long("min") => min = Some(parse_value("--min", opts.value()?)?),
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{PRUNE_USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if path.is_none() => path = Some(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let path = path.ok_or(CliError::MissingArgument("<corpus.json>"))?;
+    let mut data = Data::load(path)?;
+    let name = name.unwrap_or(data.name()).to_string();
+
+    let mut reports = Vec::new();
+
+    if let Some(chars) = chars {
+        reports.push(("chars", data.fold_rare_chars(chars)));
+    }
+    if let Some(mass) = mass {
+        reports.push(("mass", data.keep_mass(mass)));
+    }
+    if let Some(top) = top {
+        reports.push(("top", data.keep_top(top)));
+    }
+    if let Some(min) = min {
+        reports.push(("min", data.prune_below(min)));
+    }
+
+    for (step, report) in reports {
+        print!(
+            "{step:<6} {} -> {} trigrams, discarded {:.3}%",
+            report.trigrams_before,
+            report.trigrams_after,
+            report.discarded_fraction() * 100.0
+        );
+
+        match report.folded.len() {
+            0 => println!(),
+            n => println!(", folded {n} characters"),
+        }
+    }
+
+    data.renamed(&name).save(&out)?;
+    println!("saved {}", out.join(&name).with_extension("json").display());
+
+    Ok(())
+}
+
+fn refine<'a, I: Iterator<Item = &'a str>>(opts: &mut CliOptions<'a, I>) -> Result<(), CliError> {
+    let mut name = None;
+    let mut out = PathBuf::from("./data");
+    let mut flags = load::ProjectFlags::default();
+    let mut path = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('n') | Arg::Long("name") => name = Some(opts.value()?),
+            Arg::Short('o') | Arg::Long("out") => out = opts.value()?.into(),
+            Arg::Short('p') | Arg::Long("preset") | Arg::Short('r') | Arg::Long("refiner") => {
+                flags.refiner = Some(opts.value()?);
+            }
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{REFINE_USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if path.is_none() => path = Some(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let path = path.ok_or(CliError::MissingArgument("<corpus.json>"))?;
+    let config = load::project(&flags)?.refiner()?;
+
+    let data = Data::load(path)?;
+    let name = name.unwrap_or(data.name()).to_string();
+    let refined = data.refine(&config.build());
+
+    println!(
+        "{} -> {} trigrams, {} -> {} total",
+        data.inner().len(),
+        refined.inner().len(),
+        data.total(),
+        refined.total()
+    );
+
+    refined.renamed(&name).save(&out)?;
+    println!("saved {}", out.join(&name).with_extension("json").display());
+
+    Ok(())
+}
+
+/// Splits `path:weight`. The weight is split off at the last colon, so paths may contain colons.
+fn parse_source(source: &str) -> Result<(&str, f64), CliError> {
+    let invalid = || CliError::InvalidValue {
+        argument: "<corpus.json:weight>",
+        value: source.into(),
+    };
+
+    let (path, weight) = source.rsplit_once(':').ok_or_else(invalid)?;
+    let weight = weight.parse::<f64>().map_err(|_| invalid())?;
+
+    Ok((path, weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources() {
+        assert_eq!(
+            parse_source("data/shai.json:60").unwrap(),
+            ("data/shai.json", 60.0)
+        );
+        assert_eq!(
+            parse_source("C:\\data.json:0.5").unwrap(),
+            ("C:\\data.json", 0.5)
+        );
+        assert!(parse_source("data/shai.json").is_err());
+        assert!(parse_source("data/shai.json:lots").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use gen_core::prelude::{Layout, Optimizer};
+use getargs::Arg;
+use serde_json::json;
+
+use crate::{
+    analyze::print_analysis, load, parse_format, parse_value, print_json, tui, CliError,
+    CliOptions, Format,
+};
+
+const USAGE: &str = "\
+usage: gen-cli generate [options]
+
+Generates layouts by optimizing random starting layouts, and shows the best
+ones. Without --layout or --chars, the most common characters of the corpus
+are placed on a 3x10 keyboard.
+
+options:
+    -c, --corpus <corpus.json>    corpus to generate for
+    -w, --weights <weights.toml>  weights of trigram types and fingers
+    -k, --keyboard <file>         .dof or keyboard-layout-editor .json file to take
+                                  the keyboard and fingering from
+    -l, --layout <file>           layout to start from, for its characters, pins
+                                  and keyboard
+        --chars <chars>           characters to place, instead of a layout
+    -p, --pin <chars>             characters that stay where they are on --layout
+    -r, --runs <n>                amount of layouts to generate, 100 by default
+    -j, --threads <n>             amount of threads, all cores by default
+    -s, --seed <n>                seed to get the same layouts every time. A
+                                  random one is picked and printed by default
+        --top <n>                 amount of layouts to show and save, 5 by default
+    -o, --out <folder>            folder to save the best layouts in
+        --out-format <dof|text>   file format of saved layouts, dof by default
+    -n, --name <name>             name of saved layouts, numbered from best to worst
+    -f, --format <text|json>      how to print the results, text by default
+        --tui                     show the best layout, scores and speed while
+                                  generating. These only update when a run is
+                                  done, not while it's improving its layout.
+                                  Press q to stop early and keep the layouts
+                                  that are done";
+
+enum FileFormat {
+    Dof,
+    Text,
+}
+
+pub(crate) fn run<'a, I: Iterator<Item = &'a str>>(
+    opts: &mut CliOptions<'a, I>,
+) -> Result<(), CliError> {
+    let mut flags = load::ProjectFlags::default();
+    let mut runs = 100;
+    let mut threads = 0;
+    let mut seed = None;
+    let mut top = 5;
+    let mut out = None;
+    let mut file_format = FileFormat::Dof;
+    let mut name = "layout";
+    let mut format = Format::Text;
+    let mut tui = false;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('c') | Arg::Long("corpus") => flags.corpus = Some(opts.value()?),
+            Arg::Short('w') | Arg::Long("weights") => flags.weights = Some(opts.value()?),
+            Arg::Short('k') | Arg::Long("keyboard") => flags.keyboard = Some(opts.value()?),
+            Arg::Short('l') | Arg::Long("layout") => flags.layout = Some(opts.value()?),
+            Arg::Long("chars") => flags.chars = Some(opts.value()?),
+            Arg::Short('p') | Arg::Long("pin") => flags.pins = Some(opts.value()?),
+            Arg::Short('r') | Arg::Long("runs") => runs = parse_value("--runs", opts.value()?)?,
+            Arg::Short('j') | Arg::Long("threads") => {
+                threads = parse_value("--threads", opts.value()?)?
+            }
+            Arg::Short('s') | Arg::Long("seed") => {
+                seed = Some(parse_value("--seed", opts.value()?)?)
+            }
+            Arg::Long("top") => top = parse_value("--top", opts.value()?)?,
+            Arg::Short('o') | Arg::Long("out") => out = Some(PathBuf::from(opts.value()?)),
+            Arg::Long("out-format") => {
+                file_format = match opts.value()? {
+                    "dof" => FileFormat::Dof,
+                    "text" | "txt" => FileFormat::Text,
+                    value => {
+                        return Err(CliError::InvalidValue {
+                            argument: "--out-format",
+                            value: value.into(),
+                        })
+                    }
+                }
+            }
+            Arg::Short('n') | Arg::Long("name") => name = opts.value()?,
+            Arg::Short('f') | Arg::Long("format") => format = parse_format(opts.value()?)?,
+            Arg::Long("tui") => tui = true,
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let project = load::project(&flags)?;
+    let types = project.trigram_types()?;
+    let optimizer = load::optimizer(&project, &types)?;
+    let template = project.template(&optimizer)?;
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|_| CliError::InvalidValue {
+            argument: "--threads",
+            value: threads.to_string(),
+        })?;
+
+    // Picked here rather than by the batch, so it can be reported and the run reproduced.
+    let seed = seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+
+    let start = Instant::now();
+    let layouts = match tui {
+        true => tui::generate(&optimizer, &template, runs, seed, &pool)?,
+        false => pool.install(|| optimizer.generate_batch(&template, runs, Some(seed))),
+    };
+    let elapsed = start.elapsed();
+
+    if format == Format::Text {
+        println!(
+            "generated {} layouts in {elapsed:.1?} with seed {seed}\n",
+            layouts.len()
+        );
+    }
+
+    if let Some(out) = &out {
+        std::fs::create_dir_all(out).map_err(|source| CliError::WriteError {
+            path: out.display().to_string(),
+            source,
+        })?;
+    }
+
+    let mut analyses = Vec::new();
+    let mut saved = Vec::new();
+
+    for (i, (layout, _)) in layouts.iter().take(top).enumerate() {
+        let name = format!("{name}-{}", i + 1);
+
+        match format {
+            Format::Text => {
+                print_analysis(&optimizer, &name, layout);
+                println!();
+            }
+            Format::Json => analyses.push(optimizer.analyze(&name, layout)),
+        }
+
+        let Some(out) = &out else {
+            continue;
+        };
+
+        let path = match file_format {
+            FileFormat::Dof => out.join(&name).with_extension("dof"),
+            FileFormat::Text => out.join(&name).with_extension("txt"),
+        };
+
+        save_layout(&optimizer, &name, layout, &path)?;
+
+        match format {
+            Format::Text => println!("saved {}\n", path.display()),
+            Format::Json => saved.push(path.display().to_string()),
+        }
+    }
+
+    if format == Format::Json {
+        print_json(
+            "generate",
+            json!({
+                "runs": layouts.len(),
+                "seed": seed,
+                "seconds": elapsed.as_secs_f64(),
+                "layouts": analyses,
+                "saved": saved,
+            }),
+        );
+    }
+
+    Ok(())
+}
+
+/// Saves a layout as a .dof file, or in the text format for any other extension.
+pub(crate) fn save_layout(
+    optimizer: &Optimizer,
+    name: &str,
+    layout: &Layout,
+    path: &Path,
+) -> Result<(), CliError> {
+    let content = match path.extension().is_some_and(|e| e == "dof") {
+        true => {
+            let dof = layout.to_dof(name, optimizer.keyboard(), optimizer.mapping());
+            serde_json::to_string_pretty(&dof).expect("dofs can always be serialized")
+        }
+        false => optimizer.layout_to_str(layout) + "\n",
+    };
+
+    std::fs::write(path, content).map_err(|source| CliError::WriteError {
+        path: path.display().to_string(),
+        source,
+    })
+}
@@ -1,22 +1,159 @@
+mod analyze;
+mod compare;
+mod corpus;
+mod generate;
+mod load;
+mod rank;
+mod repl;
+mod tui;
+mod watch;
+
+use gen_core::prelude::{DataError, LayoutError, ProjectError, SCHEMA_VERSION};
+use getargs::{Arg, Options};
 use thiserror::Error;
 
+const USAGE: &str = "\
+usage: gen-cli <command> [options]
+
+commands:
+    analyze         score a layout and show its statistics
+    compare         show the statistics of two layouts side by side
+    generate        generate layouts for a corpus
+    rank            score and compare every layout in a folder
+    repl            analyze and generate layouts interactively
+    corpus build    count the trigrams of a text file or folder
+    corpus blend    blend several corpora into a new one
+    corpus inspect  show how well a refiner fits a text, or what a corpus contains
+    corpus prune    drop rare trigrams so optimizing with a corpus is faster
+    corpus refine   clean up a corpus that was already counted, without its text
+
+If the working directory has an oxeygen.toml, the corpus, weights, keyboard,
+refiner and layout are taken from it, and flags override what it sets.";
+
 #[derive(Debug, Error)]
 pub enum CliError {
-    #[error("Couldn't parse the thing you sent lmfao")]
-    ParseError,
+    #[error("{0}")]
+    ArgumentError(String),
+
+    #[error("Unknown command '{0}'")]
+    UnknownCommand(String),
+
+    #[error("Unknown option '{0}'")]
+    UnknownOption(String),
+
+    #[error("Missing required argument: {0}")]
+    MissingArgument(&'static str),
+
+    #[error("Invalid value '{value}' for {argument}")]
+    InvalidValue {
+        argument: &'static str,
+        value: String,
+    },
+
+    #[error("{0}")]
+    DataError(#[from] DataError),
+
+    #[error("Couldn't read '{path}': {source}")]
+    ReadError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Couldn't write '{path}': {source}")]
+    WriteError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("{0}")]
+    ProjectError(#[from] ProjectError),
+
+    #[error("Invalid layout: {0}")]
+    LayoutError(#[from] LayoutError),
+
+    #[error("Couldn't use the terminal: {0}")]
+    TerminalError(std::io::Error),
+
+    #[error("Unknown layout '{0}'")]
+    UnknownLayout(String),
+
+    #[error("No working layout, `load` or `generate` one first")]
+    NoWorkingLayout,
+
+    #[error("'{0}' is pinned, so it can't be swapped")]
+    PinnedKey(String),
+}
+
+impl<'a> From<getargs::Error<&'a str>> for CliError {
+    fn from(err: getargs::Error<&'a str>) -> Self {
+        Self::ArgumentError(err.to_string())
+    }
+}
+
+type CliOptions<'a, I> = Options<&'a str, I>;
+
+fn parse_value<T: std::str::FromStr>(argument: &'static str, value: &str) -> Result<T, CliError> {
+    value.parse().map_err(|_| CliError::InvalidValue {
+        argument,
+        value: value.into(),
+    })
+}
+
+/// How a command prints its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn parse_format(value: &str) -> Result<Format, CliError> {
+    match value {
+        "text" => Ok(Format::Text),
+        "json" => Ok(Format::Json),
+        _ => Err(CliError::InvalidValue {
+            argument: "--format",
+            value: value.into(),
+        }),
+    }
+}
+
+/// Prints the fields of a command's result as a JSON object, together with the version of the
+/// schema and the name of the command.
+fn print_json(command: &str, result: serde_json::Value) {
+    let mut output = serde_json::Map::new();
+    output.insert("version".into(), SCHEMA_VERSION.into());
+    output.insert("command".into(), command.into());
+
+    if let serde_json::Value::Object(fields) = result {
+        output.extend(fields);
+    }
+
+    let output = serde_json::to_string_pretty(&output).expect("results can always be serialized");
+    println!("{output}");
 }
 
 pub fn cli() -> Result<(), CliError> {
-    // let keyboard = IsoAngle::keyboard();
-    // let corpus = Corpus::load("./data/shai.json").expect("this should not fail");
-    // let keyboard_stats = KeyboardStats::default_types(keyboard, corpus);
-    // let layout_keys: [char; 30] = "bgdlzjfou,nstrkycaeiqvmhxpw';."
-    //     .chars()
-    //     .collect::<Vec<_>>()
-    //     .try_into()
-    //     .unwrap();
-    // let layout = keyboard_stats.layout_with(layout_keys);
-    // keyboard_stats.print_stats(&layout);
-
-    Ok(())
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    run(args.iter().map(String::as_str))
+}
+
+/// Runs the command described by `args`, without the name of the binary.
+pub fn run<'a, I: Iterator<Item = &'a str>>(args: I) -> Result<(), CliError> {
+    let mut opts = Options::new(args);
+
+    match opts.next_arg()? {
+        Some(Arg::Positional("analyze")) => analyze::run(&mut opts),
+        Some(Arg::Positional("compare")) => compare::run(&mut opts),
+        Some(Arg::Positional("corpus")) => corpus::run(&mut opts),
+        Some(Arg::Positional("generate")) => generate::run(&mut opts),
+        Some(Arg::Positional("rank")) => rank::run(&mut opts),
+        Some(Arg::Positional("repl")) => repl::run(&mut opts),
+        Some(Arg::Positional(command)) => Err(CliError::UnknownCommand(command.into())),
+        Some(Arg::Short('h') | Arg::Long("help")) | None => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(arg) => Err(CliError::UnknownOption(arg.to_string())),
+    }
 }
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use gen_core::prelude::{KeyboardSource, Optimizer, Project, Source, TrigramTypes, WeightsWarning};
+
+use crate::CliError;
+
+/// Flags that override what's in the project file.
+#[derive(Default)]
+pub(crate) struct ProjectFlags<'a> {
+    pub corpus: Option<&'a str>,
+    pub weights: Option<&'a str>,
+    pub keyboard: Option<&'a str>,
+    pub refiner: Option<&'a str>,
+    pub layout: Option<&'a str>,
+    pub chars: Option<&'a str>,
+    pub pins: Option<&'a str>,
+}
+
+/// Loads the project file in the working directory if there is one, with `flags` applied on top.
+pub(crate) fn project(flags: &ProjectFlags) -> Result<Project, CliError> {
+    let mut project = Project::discover(".")?.unwrap_or_default();
+
+    if let Some(corpus) = flags.corpus {
+        project.corpus = Some(corpus.into());
+    }
+    if let Some(weights) = flags.weights {
+        project.weights = Some(Source::Path(weights.into()));
+    }
+    if let Some(keyboard) = flags.keyboard {
+        project.keyboard = Some(KeyboardSource::Path(keyboard.into()));
+    }
+    if let Some(refiner) = flags.refiner {
+        project.refiner = Some(Source::Path(refiner.into()));
+    }
+    if let Some(layout) = flags.layout {
+        project.layout = Some(layout.into());
+        project.chars = None;
+    }
+    if let Some(chars) = flags.chars {
+        project.chars = Some(chars.into());
+        project.layout = None;
+    }
+    if let Some(pins) = flags.pins {
+        project.pins = Some(pins.into());
+    }
+
+    Ok(project)
+}
+
+/// Creates the optimizer of `project`, and prints what was suspicious about its weights.
+pub(crate) fn optimizer<'a>(
+    project: &Project,
+    types: &'a TrigramTypes<'a>,
+) -> Result<Optimizer<'a>, CliError> {
+    let (optimizer, warnings) = project.optimizer(types)?;
+    print_warnings(&warnings);
+
+    Ok(optimizer)
+}
+
+/// Prints warnings about weights to stderr, so they don't end up in piped output.
+pub(crate) fn print_warnings(warnings: &[WeightsWarning]) {
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+}
+
+/// Lists the files in a folder, sorted by path.
+pub(crate) fn files(folder: &str) -> Result<Vec<PathBuf>, CliError> {
+    let entries = std::fs::read_dir(folder).map_err(|source| CliError::ReadError {
+        path: folder.into(),
+        source,
+    })?;
+
+    let mut paths = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(paths)
+}
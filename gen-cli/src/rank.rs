@@ -0,0 +1,233 @@
+use gen_core::prelude::{
+    Layout, LayoutAnalysis, LayoutFile, Optimizer, ProjectError, TrigramTypes,
+};
+use getargs::Arg;
+use serde_json::json;
+
+use crate::{load, parse_format, print_json, CliError, CliOptions, Format};
+
+const USAGE: &str = "\
+usage: gen-cli rank [options] <folder>
+
+Scores every layout in a folder with the same corpus, weights and keyboard, and
+prints them as a table. Layouts that don't fit the keyboard, or that have
+characters the corpus doesn't, are skipped.
+
+options:
+    -c, --corpus <corpus.json>    corpus to score with
+    -w, --weights <weights.toml>  weights of trigram types and fingers
+    -k, --keyboard <file>         .dof or keyboard-layout-editor .json file to take
+                                  the keyboard and fingering from, 3x10 by default
+        --columns <types>         comma separated trigram types to show,
+                                  sfb,sfr,inroll,outroll,alternation,redirect by default
+    -s, --sort <column>           column to sort by from high to low, score by default
+    -r, --reverse                 sort from low to high instead
+    -f, --format <text|json>      how to print the results, text by default";
+
+const DEFAULT_COLUMNS: &str = "sfb,sfr,inroll,outroll,alternation,redirect";
+
+pub(crate) fn run<'a, I: Iterator<Item = &'a str>>(
+    opts: &mut CliOptions<'a, I>,
+) -> Result<(), CliError> {
+    let mut flags = load::ProjectFlags::default();
+    let mut columns = DEFAULT_COLUMNS;
+    let mut sort = "score";
+    let mut reverse = false;
+    let mut format = Format::Text;
+    let mut folder = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('c') | Arg::Long("corpus") => flags.corpus = Some(opts.value()?),
+            Arg::Short('w') | Arg::Long("weights") => flags.weights = Some(opts.value()?),
+            Arg::Short('k') | Arg::Long("keyboard") => flags.keyboard = Some(opts.value()?),
+            Arg::Long("columns") => columns = opts.value()?,
+            Arg::Short('s') | Arg::Long("sort") => sort = opts.value()?,
+            Arg::Short('r') | Arg::Long("reverse") => reverse = true,
+            Arg::Short('f') | Arg::Long("format") => format = parse_format(opts.value()?)?,
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            Arg::Positional(p) if folder.is_none() => folder = Some(p),
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let folder = folder.ok_or(CliError::MissingArgument("<folder>"))?;
+    let project = load::project(&flags)?;
+
+    let types = project.trigram_types()?;
+    let columns = columns
+        .split(',')
+        .map(|c| trigram_type(&types, c, "--columns"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sort = match sort {
+        "score" => None,
+        column => Some(trigram_type(&types, column, "--sort")?),
+    };
+
+    let optimizer = load::optimizer(&project, &types)?;
+    let (layouts, skipped) = load_layouts(&optimizer, folder)?;
+
+    let mut analyses = layouts
+        .iter()
+        .map(|(name, layout)| optimizer.analyze(name, layout))
+        .collect::<Vec<_>>();
+
+    let key = |a: &LayoutAnalysis| match sort {
+        Some(column) => a.trigram_type(column),
+        None => a.score,
+    };
+    analyses.sort_by(|a1, a2| match reverse {
+        true => key(a1).total_cmp(&key(a2)),
+        false => key(a2).total_cmp(&key(a1)),
+    });
+
+    if format == Format::Json {
+        let skipped = skipped
+            .iter()
+            .map(|(path, reason)| json!({ "path": path, "reason": reason.to_string() }))
+            .collect::<Vec<_>>();
+
+        print_json("rank", json!({ "layouts": analyses, "skipped": skipped }));
+
+        return Ok(());
+    }
+
+    for (path, reason) in &skipped {
+        eprintln!("skipped {path}: {reason}");
+    }
+
+    let width = analyses
+        .iter()
+        .map(|a| a.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let width = width.max("name".len());
+
+    print!("{:<width$}  {:>9}", "name", "score");
+    for column in &columns {
+        print!("  {column:>12}");
+    }
+    println!();
+
+    for analysis in analyses {
+        print!("{:<width$}  {:>9.3}", analysis.name, analysis.score);
+        for column in &columns {
+            print!("  {:>11.3}%", analysis.trigram_type(column));
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Finds the trigram type a column refers to. Case, spaces, dashes and underscores are ignored,
+/// so `onehand-in` means `Onehand In`.
+fn trigram_type<'t>(
+    types: &'t TrigramTypes,
+    column: &str,
+    argument: &'static str,
+) -> Result<&'t str, CliError> {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+
+    let column_name = normalize(column);
+
+    types
+        .types()
+        .iter()
+        .chain(std::iter::once(&types.default()))
+        .map(|t| t.display())
+        .find(|name| normalize(name) == column_name)
+        .ok_or_else(|| CliError::InvalidValue {
+            argument,
+            value: column.into(),
+        })
+}
+
+/// Layouts by name, and the paths of files that were skipped with the reason why.
+type Loaded = (Vec<(String, Layout)>, Vec<(String, ProjectError)>);
+
+/// Loads every layout in `folder` for the keyboard of `optimizer`. Files that can't be read or
+/// don't fit are returned separately, with the reason they were skipped.
+fn load_layouts(optimizer: &Optimizer, folder: &str) -> Result<Loaded, CliError> {
+    let mut layouts = Vec::new();
+    let mut skipped = Vec::new();
+
+    for path in load::files(folder)? {
+        let path = path.to_string_lossy().into_owned();
+
+        let res = LayoutFile::load(&path)
+            .and_then(|file| Ok((file.name().to_string(), file.layout(optimizer)?)));
+
+        match res {
+            Ok(layout) => layouts.push(layout),
+            Err(reason) => skipped.push((path, reason)),
+        }
+    }
+
+    Ok((layouts, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gen_core::prelude::{Data, Keyboard, LayoutError, Weights};
+
+    #[test]
+    fn layouts() {
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+
+        assert_eq!(trigram_type(&types, "sfb", "").unwrap(), "Sfb");
+        assert_eq!(
+            trigram_type(&types, "onehand-in", "").unwrap(),
+            "Onehand In"
+        );
+        assert!(trigram_type(&types, "scissors", "").is_err());
+
+        let data = Data::load("../data/shai.json").unwrap();
+        let weights = Weights::load("../gen-core/weights.toml").unwrap();
+        let optimizer = Optimizer::new(&types, &data, weights);
+
+        let folder = std::env::temp_dir().join("oxeygen-rank");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(
+            folder.join("qwerty.txt"),
+            "q w e r t y u i o p\na s d f g h j k l ;\nz x c v b n m , . '",
+        )
+        .unwrap();
+        std::fs::write(
+            folder.join("short.txt"),
+            "q w e r t y u i o p\na s d f g h j k l ;",
+        )
+        .unwrap();
+        std::fs::write(
+            folder.join("euro.txt"),
+            "q w e r t y u i o p\na s d f g h j k l €\nz x c v b n m , . '",
+        )
+        .unwrap();
+
+        let (layouts, skipped) = load_layouts(&optimizer, &folder.to_string_lossy()).unwrap();
+
+        assert_eq!(layouts.len(), 1);
+        assert_eq!(layouts[0].0, "qwerty");
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped[0].0.ends_with("euro.txt"));
+        assert!(matches!(
+            skipped[0].1,
+            ProjectError::LayoutError(LayoutError::UnknownChar('€'))
+        ));
+        assert!(matches!(
+            skipped[1].1,
+            ProjectError::LayoutError(LayoutError::RowCount { .. })
+        ));
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
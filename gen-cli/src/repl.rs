@@ -0,0 +1,404 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use gen_core::prelude::{Layout, LayoutFile, Optimizer};
+use getargs::Arg;
+
+use crate::{
+    analyze::print_analysis, compare::print_comparison, generate::save_layout, load, parse_value,
+    CliError, CliOptions,
+};
+
+const USAGE: &str = "\
+usage: gen-cli repl [options]
+
+Loads a corpus, weights and keyboard once, and then reads commands from stdin,
+so analyzing and generating layouts doesn't have to set up the optimizer every
+time. Type `help` to see the commands.
+
+options:
+    -c, --corpus <corpus.json>    corpus to analyze with
+    -w, --weights <weights.toml>  weights of trigram types and fingers
+    -k, --keyboard <file>         .dof or keyboard-layout-editor .json file to take
+                                  the keyboard and fingering from, 3x10 by default
+    -l, --layouts <folder>        folder with .dof and text layouts to load";
+
+const COMMANDS: &str = "\
+commands:
+    analyze [name]      show the statistics of a layout, the working one by default
+    compare <a> <b>     show the statistics of two layouts side by side
+    swap <c1> <c2>      swap two unpinned characters on the working layout
+    generate [n]        generate n layouts from the working one, 10 by default
+    improve             apply the best swaps to the working layout until none are left
+    rank                list all layouts from best to worst
+    save <path>         save the working layout as .dof, or as text for other extensions
+    load <name|path>    make a layout the working one, loading it from a file if needed
+    help                show this message
+    quit                leave the repl";
+
+pub(crate) fn run<'a, I: Iterator<Item = &'a str>>(
+    opts: &mut CliOptions<'a, I>,
+) -> Result<(), CliError> {
+    let mut flags = load::ProjectFlags::default();
+    let mut folder = None;
+
+    while let Some(arg) = opts.next_arg()? {
+        match arg {
+            Arg::Short('c') | Arg::Long("corpus") => flags.corpus = Some(opts.value()?),
+            Arg::Short('w') | Arg::Long("weights") => flags.weights = Some(opts.value()?),
+            Arg::Short('k') | Arg::Long("keyboard") => flags.keyboard = Some(opts.value()?),
+            Arg::Short('l') | Arg::Long("layouts") => folder = Some(opts.value()?),
+            Arg::Short('h') | Arg::Long("help") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            arg => return Err(CliError::UnknownOption(arg.to_string())),
+        }
+    }
+
+    let project = load::project(&flags)?;
+    let types = project.trigram_types()?;
+    let optimizer = load::optimizer(&project, &types)?;
+    let template = project.template(&optimizer)?;
+    let mut repl = Repl::new(optimizer, template);
+
+    if let Some(folder) = folder {
+        repl.load_folder(folder)?;
+    }
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("> ");
+        std::io::stdout().flush().ok();
+
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+
+        match repl.execute(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("error: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// The state of the repl: an optimizer, the layouts it knows by name and the layout that's being
+/// worked on.
+struct Repl<'a> {
+    optimizer: Optimizer<'a>,
+    /// Layout to generate from when there's no working layout.
+    template: Layout,
+    layouts: BTreeMap<String, Layout>,
+    working: Option<(String, Layout)>,
+}
+
+impl<'a> Repl<'a> {
+    fn new(optimizer: Optimizer<'a>, template: Layout) -> Self {
+        Self {
+            optimizer,
+            template,
+            layouts: BTreeMap::new(),
+            working: None,
+        }
+    }
+
+    /// Loads every layout in a folder. Layouts that don't fit the keyboard are skipped.
+    fn load_folder(&mut self, folder: &str) -> Result<(), CliError> {
+        for path in load::files(folder)? {
+            let path = path.to_string_lossy();
+
+            match self.load_file(&path) {
+                Ok(name) => println!("loaded {name}"),
+                Err(e) => println!("skipped {path}: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &str) -> Result<String, CliError> {
+        let file = LayoutFile::load(path)?;
+        let layout = file.layout(&self.optimizer)?;
+        let name = file.name().to_string();
+
+        self.layouts.insert(name.clone(), layout);
+
+        Ok(name)
+    }
+
+    /// Executes a single line of input. Returns `false` if the repl should stop.
+    fn execute(&mut self, line: &str) -> Result<bool, CliError> {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+
+        match args.as_slice() {
+            [] => {}
+            ["analyze" | "a"] => {
+                let (name, layout) = self.working()?;
+                print_analysis(&self.optimizer, name, layout);
+            }
+            ["analyze" | "a", name] => print_analysis(&self.optimizer, name, self.get(name)?),
+            ["compare" | "c", a, b] => self.compare(a, b)?,
+            ["swap" | "s", c1, c2] => self.swap(c1, c2)?,
+            ["generate" | "g"] => self.generate(10)?,
+            ["generate" | "g", n] => self.generate(parse_value("generate", n)?)?,
+            ["improve" | "i"] => self.improve()?,
+            ["rank" | "r"] => self.rank(),
+            ["save", path] => self.save(path)?,
+            ["load" | "l", name] => self.load(name)?,
+            ["help" | "h"] => println!("{COMMANDS}"),
+            ["quit" | "q" | "exit"] => return Ok(false),
+            [command, ..] => return Err(CliError::UnknownCommand(command.to_string())),
+        }
+
+        Ok(true)
+    }
+
+    fn working(&self) -> Result<(&str, &Layout), CliError> {
+        match &self.working {
+            Some((name, layout)) => Ok((name, layout)),
+            None => Err(CliError::NoWorkingLayout),
+        }
+    }
+
+    fn working_mut(&mut self) -> Result<&mut Layout, CliError> {
+        match &mut self.working {
+            Some((_, layout)) => Ok(layout),
+            None => Err(CliError::NoWorkingLayout),
+        }
+    }
+
+    /// Finds a layout by name. The working layout goes first, since it may have been changed.
+    fn get(&self, name: &str) -> Result<&Layout, CliError> {
+        match &self.working {
+            Some((working, layout)) if working == name => Ok(layout),
+            _ => self
+                .layouts
+                .get(name)
+                .ok_or_else(|| CliError::UnknownLayout(name.into())),
+        }
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Result<(), CliError> {
+        let layouts = [(a, self.get(a)?.clone()), (b, self.get(b)?.clone())];
+        print_comparison(&self.optimizer, &layouts);
+
+        Ok(())
+    }
+
+    fn swap(&mut self, c1: &str, c2: &str) -> Result<(), CliError> {
+        let p1 = self.position(c1)?;
+        let p2 = self.position(c2)?;
+
+        let layout = self.working()?.1;
+        if let Some((c, _)) = [(c1, p1), (c2, p2)]
+            .into_iter()
+            .find(|&(_, p)| layout.is_pinned(p))
+        {
+            return Err(CliError::PinnedKey(c.into()));
+        }
+
+        let before = self.optimizer.calc_score(layout);
+        self.working_mut()?
+            .swap_keys(p1, p2)
+            .expect("positions are on the layout and not pinned");
+        let layout = self.working()?.1;
+
+        println!("{}\n", self.optimizer.layout_to_str(layout));
+        println!(
+            "score: {before:.3} -> {:.3}",
+            self.optimizer.calc_score(layout)
+        );
+
+        Ok(())
+    }
+
+    fn position(&self, c: &str) -> Result<usize, CliError> {
+        let mut chars = c.chars();
+        let invalid = || CliError::InvalidValue {
+            argument: "swap",
+            value: c.into(),
+        };
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => self
+                .optimizer
+                .position(self.working()?.1, c)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Generates `n` layouts from the working layout, or from the template of the project if there
+    /// is none. The best ten are kept, and the best one becomes the working layout.
+    fn generate(&mut self, n: usize) -> Result<(), CliError> {
+        let template = match &self.working {
+            Some((_, layout)) => layout,
+            None => &self.template,
+        };
+
+        let layouts = self.optimizer.generate_batch(template, n, None);
+
+        for (i, (layout, score)) in layouts.into_iter().take(10).enumerate() {
+            let name = format!("generated-{}", i + 1);
+            println!("{score:>8.3}  {name}");
+
+            if i == 0 {
+                self.working = Some((name.clone(), layout.clone()));
+            }
+            self.layouts.insert(name, layout);
+        }
+
+        if let Some((name, layout)) = &self.working {
+            println!();
+            print_analysis(&self.optimizer, name, layout);
+        }
+
+        Ok(())
+    }
+
+    fn improve(&mut self) -> Result<(), CliError> {
+        let before = self.optimizer.calc_score(self.working()?.1);
+        let mut layout = self.working()?.1.clone();
+        self.optimizer.optimize(&mut layout);
+
+        println!("{}\n", self.optimizer.layout_to_str(&layout));
+        println!(
+            "score: {before:.3} -> {:.3}",
+            self.optimizer.calc_score(&layout)
+        );
+
+        *self.working_mut()? = layout;
+
+        Ok(())
+    }
+
+    fn rank(&self) {
+        let mut ranked = self
+            .layouts
+            .iter()
+            .map(|(name, layout)| (name.clone(), self.optimizer.calc_score(layout)))
+            .collect::<Vec<_>>();
+
+        if let Some((name, layout)) = &self.working {
+            if self.layouts.get(name) != Some(layout) {
+                ranked.push((
+                    format!("{name} (working)"),
+                    self.optimizer.calc_score(layout),
+                ));
+            }
+        }
+
+        ranked.sort_by(|(_, s1), (_, s2)| s2.total_cmp(s1));
+
+        for (name, score) in ranked {
+            println!("{score:>8.3}  {name}");
+        }
+    }
+
+    /// Saves the working layout, which is then also known under the name of the file.
+    fn save(&mut self, path: &str) -> Result<(), CliError> {
+        let path = Path::new(path);
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+
+        let layout = self.working()?.1.clone();
+        save_layout(&self.optimizer, &name, &layout, path)?;
+        println!("saved {}", path.display());
+
+        self.layouts.insert(name.clone(), layout.clone());
+        self.working = Some((name, layout));
+
+        Ok(())
+    }
+
+    fn load(&mut self, name: &str) -> Result<(), CliError> {
+        let name = match self.layouts.contains_key(name) {
+            true => name.to_string(),
+            false => self.load_file(name)?,
+        };
+
+        let layout = self.layouts[&name].clone();
+        println!("{}", self.optimizer.layout_to_str(&layout));
+        self.working = Some((name, layout));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gen_core::prelude::{Data, Keyboard, TrigramTypes, Weights};
+
+    #[test]
+    fn commands() {
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+        let data = Data::load("../data/shai.json").unwrap();
+        let weights = Weights::load("../gen-core/weights.toml").unwrap();
+        let optimizer = Optimizer::new(&types, &data, weights);
+        let chars = "abcdefghijklmnopqrstuvwxyz',.;".chars().collect::<Vec<_>>();
+        let template = optimizer
+            .layout(&chars, optimizer.keyboard().fingering())
+            .unwrap();
+        let mut repl = Repl::new(optimizer, template);
+
+        let folder = std::env::temp_dir().join("oxeygen-repl");
+        std::fs::create_dir_all(&folder).unwrap();
+        let qwerty = folder.join("qwerty.txt");
+        std::fs::write(
+            &qwerty,
+            "q w e r t y u i o p\na s d f g h j k l ;\nz x c v b n m , . '",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            repl.execute("swap a e"),
+            Err(CliError::NoWorkingLayout)
+        ));
+        assert!(repl.execute(&format!("load {}", qwerty.display())).unwrap());
+        assert!(repl.execute("swap a e").unwrap());
+
+        let layout = repl.get("qwerty").unwrap();
+        assert_eq!(repl.optimizer.position(layout, 'a'), Some(2));
+        assert_ne!(repl.layouts["qwerty"], repl.working.as_ref().unwrap().1);
+
+        let pinned = repl.working.as_ref().unwrap().1.clone();
+        repl.working_mut().unwrap().pin(2);
+        assert!(matches!(
+            repl.execute("swap s a"),
+            Err(CliError::PinnedKey(c)) if c == "a"
+        ));
+        repl.working_mut().unwrap().unpin(2);
+        assert_eq!(repl.working.as_ref().unwrap().1, pinned);
+
+        let saved = folder.join("swapped.dof");
+        assert!(repl.execute(&format!("save {}", saved.display())).unwrap());
+        assert!(repl.execute("compare qwerty swapped").unwrap());
+        assert!(repl.execute("improve").unwrap());
+        assert!(repl.execute("rank").unwrap());
+
+        let swapped = repl.layouts["swapped"].clone();
+        repl.layouts.clear();
+        repl.load_folder(&folder.to_string_lossy()).unwrap();
+        assert_eq!(repl.layouts["swapped"], swapped);
+        assert_eq!(repl.layouts.len(), 2);
+
+        assert!(matches!(
+            repl.execute("analyze colemak"),
+            Err(CliError::UnknownLayout(_))
+        ));
+        assert!(!repl.execute("quit").unwrap());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
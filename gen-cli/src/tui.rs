@@ -0,0 +1,281 @@
+use std::{
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use gen_core::prelude::{Layout, LayoutAnalysis, Optimizer};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout as Split},
+    widgets::{Block, Gauge, Paragraph, Sparkline},
+    DefaultTerminal, Frame,
+};
+use rayon::ThreadPool;
+use unicode_width::UnicodeWidthStr;
+
+use crate::CliError;
+
+/// Amount of runs whose scores are kept for the sparkline.
+const HISTORY: usize = 512;
+
+/// Generates a batch of layouts on `pool` while showing a dashboard of how it's going. Pressing
+/// q, escape or ctrl-c stops early, in which case only the layouts that were finished are
+/// returned. Layouts are sorted from best to worst.
+pub(crate) fn generate(
+    optimizer: &Optimizer,
+    template: &Layout,
+    runs: usize,
+    seed: u64,
+    pool: &ThreadPool,
+) -> Result<Vec<(Layout, f32)>, CliError> {
+    let mut terminal = ratatui::try_init().map_err(CliError::TerminalError)?;
+    let mut dashboard = Dashboard::new(runs);
+    let (sender, receiver) = mpsc::channel();
+    let stop = AtomicBool::new(false);
+
+    let (layouts, result) = std::thread::scope(|s| {
+        let worker = s.spawn(|| {
+            pool.install(|| {
+                optimizer.generate_batch_with_progress(template, runs, Some(seed), |p| {
+                    let _ = sender.send((p.layout.clone(), p.score));
+
+                    match stop.load(Ordering::Relaxed) {
+                        true => ControlFlow::Break(()),
+                        false => ControlFlow::Continue(()),
+                    }
+                })
+            })
+        });
+
+        let result = run(
+            &mut terminal,
+            &mut dashboard,
+            optimizer,
+            &receiver,
+            &stop,
+            || worker.is_finished(),
+        );
+        stop.store(true, Ordering::Relaxed);
+
+        (worker.join().expect("generating layouts panicked"), result)
+    });
+
+    ratatui::restore();
+    result.map_err(CliError::TerminalError)?;
+
+    Ok(layouts)
+}
+
+/// Draws the dashboard until `finished` returns true, and handles key presses in between.
+fn run(
+    terminal: &mut DefaultTerminal,
+    dashboard: &mut Dashboard,
+    optimizer: &Optimizer,
+    receiver: &mpsc::Receiver<(Layout, f32)>,
+    stop: &AtomicBool,
+    finished: impl Fn() -> bool,
+) -> std::io::Result<()> {
+    loop {
+        for (layout, score) in receiver.try_iter() {
+            dashboard.update(optimizer, layout, score);
+        }
+
+        terminal.draw(|frame| dashboard.draw(frame))?;
+
+        if finished() {
+            return Ok(());
+        }
+
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL;
+            let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c;
+
+            if key.kind == KeyEventKind::Press && quit {
+                stop.store(true, Ordering::Relaxed);
+                dashboard.stopping = true;
+            }
+        }
+    }
+}
+
+struct Dashboard {
+    runs: usize,
+    done: usize,
+    start: Instant,
+    best: Option<(LayoutAnalysis, f32)>,
+    scores: Vec<f32>,
+    stopping: bool,
+}
+
+impl Dashboard {
+    fn new(runs: usize) -> Self {
+        Self {
+            runs,
+            done: 0,
+            start: Instant::now(),
+            best: None,
+            scores: Vec::new(),
+            stopping: false,
+        }
+    }
+
+    fn update(&mut self, optimizer: &Optimizer, layout: Layout, score: f32) {
+        self.done += 1;
+
+        if self.scores.len() == HISTORY {
+            self.scores.remove(0);
+        }
+        self.scores.push(score);
+
+        if self.best.as_ref().is_none_or(|(_, best)| score > *best) {
+            self.best = Some((optimizer.analyze("best", &layout), score));
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, middle, footer] = Split::vertical([
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(6),
+        ])
+        .areas(frame.area());
+        let [grid, types] =
+            Split::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(middle);
+
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = self.done as f64 / elapsed.max(f64::EPSILON);
+        let best = self.best.as_ref().map(|(_, s)| format!("{s:.3}"));
+        let status = match self.stopping {
+            true => "stopping after the current runs",
+            false => "q to stop",
+        };
+
+        let progress = Gauge::default()
+            .block(Block::bordered().title(" generate "))
+            .ratio(self.done as f64 / self.runs.max(1) as f64)
+            .label(format!(
+                "{}/{} runs · {rate:.1} runs/s · best {} · {status}",
+                self.done,
+                self.runs,
+                best.as_deref().unwrap_or("-"),
+            ));
+        frame.render_widget(progress, header);
+
+        let (keys, trigram_types) = match &self.best {
+            Some((analysis, _)) => (
+                keyboard_grid(&analysis.rows).join("\n"),
+                analysis
+                    .trigram_types
+                    .iter()
+                    .map(|f| format!("{:<14}{:>7.3}%", f.name, f.percentage))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            None => Default::default(),
+        };
+
+        let keys = Paragraph::new(keys).block(Block::bordered().title(" best layout "));
+        frame.render_widget(keys, grid);
+
+        let trigram_types =
+            Paragraph::new(trigram_types).block(Block::bordered().title(" trigram types "));
+        frame.render_widget(trigram_types, types);
+
+        let width = footer.width.saturating_sub(2) as usize;
+        let recent = &self.scores[self.scores.len().saturating_sub(width)..];
+        let sparkline = Sparkline::default()
+            .block(Block::bordered().title(" score per run "))
+            .data(scaled(recent));
+        frame.render_widget(sparkline, footer);
+    }
+}
+
+/// Scales scores to between 1 and 100, so the worst one is still visible in a sparkline.
+fn scaled(scores: &[f32]) -> Vec<u64> {
+    let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+
+    scores
+        .iter()
+        .map(|s| 1 + ((s - min) / range * 99.0) as u64)
+        .collect()
+}
+
+/// Draws rows of keys as a grid of boxes, all as wide as the widest key.
+fn keyboard_grid(rows: &[Vec<String>]) -> Vec<String> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let cell = rows.iter().flatten().map(|k| k.width()).max().unwrap_or(1);
+    let line = "─".repeat(cell + 2);
+    let border =
+        |left, middle, right| format!("{left}{}{right}", vec![line.as_str(); width].join(middle));
+
+    let mut lines = vec![border('┌', "┬", '┐')];
+
+    for (i, row) in rows.iter().enumerate() {
+        if i > 0 {
+            lines.push(border('├', "┼", '┤'));
+        }
+
+        let keys = (0..width)
+            .map(|c| {
+                let key = row.get(c).map_or("", |k| k.as_str());
+                format!(" {key}{} ", " ".repeat(cell - key.width()))
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!("│{}│", keys.join("│")));
+    }
+
+    lines.push(border('└', "┴", '┘'));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let rows = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["c".to_string()],
+        ];
+
+        assert_eq!(
+            keyboard_grid(&rows),
+            [
+                "┌───┬───┐",
+                "│ a │ b │",
+                "├───┼───┤",
+                "│ c │   │",
+                "└───┴───┘"
+            ]
+        );
+
+        let rows = vec![
+            vec!["spc".to_string(), "é".to_string()],
+            vec!["あ".to_string()],
+        ];
+
+        assert_eq!(
+            keyboard_grid(&rows),
+            [
+                "┌─────┬─────┐",
+                "│ spc │ é   │",
+                "├─────┼─────┤",
+                "│ あ  │     │",
+                "└─────┴─────┘"
+            ]
+        );
+        assert_eq!(scaled(&[-2.0, -1.0, 0.0]), [1, 50, 100]);
+    }
+}
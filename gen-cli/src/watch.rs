@@ -0,0 +1,303 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use gen_core::{
+    prelude::{
+        Data, Keyboard, KeyboardSource, LayoutFile, Optimizer, Project, ProjectError, Source,
+        TrigramTypes, Weights,
+    },
+    project::PROJECT_FILE,
+};
+
+use crate::{analyze, load, CliError, Format};
+
+/// How often files are checked for changes.
+const POLL: Duration = Duration::from_millis(300);
+
+/// What a changed file affects, from least to most that has to be rebuilt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    Layout,
+    Weights,
+    Keyboard,
+    Project,
+    Corpus,
+}
+
+/// Modification times of the files an analysis depends on.
+#[derive(Default)]
+struct Watcher {
+    files: Vec<(Change, PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    /// Starts watching the layout and the files of `project`, as they are right now. Without a
+    /// project, because it couldn't be loaded, only the layout and project file are watched.
+    fn track(&mut self, project: Option<&Project>, layout: &str) {
+        let mut files = vec![
+            (Change::Layout, PathBuf::from(layout)),
+            (Change::Project, PathBuf::from(PROJECT_FILE)),
+        ];
+
+        if let Some(project) = project {
+            if let Some(Source::Path(path)) = &project.weights {
+                files.push((Change::Weights, path.clone()));
+            }
+            if let Some(KeyboardSource::Path(path)) = &project.keyboard {
+                files.push((Change::Keyboard, path.clone()));
+            }
+            if let Some(path) = &project.corpus {
+                files.push((Change::Corpus, path.clone()));
+            }
+        }
+
+        self.files = files
+            .into_iter()
+            .map(|(change, path)| {
+                let modified = modified(&path);
+                (change, path, modified)
+            })
+            .collect();
+    }
+
+    /// Blocks until at least one file changes, and returns the biggest change.
+    fn wait(&mut self) -> Change {
+        loop {
+            std::thread::sleep(POLL);
+
+            if let Some(change) = self.changed() {
+                return change;
+            }
+        }
+    }
+
+    /// The biggest change since the last check, if any file changed.
+    fn changed(&mut self) -> Option<Change> {
+        self.files
+            .iter_mut()
+            .filter_map(|(change, path, last)| {
+                let modified = modified(path);
+                let changed = modified != *last;
+                *last = modified;
+
+                changed.then_some(*change)
+            })
+            .max()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// What has to be rebuilt once a session ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rebuild {
+    /// The optimizer for a new keyboard or project file, reusing the tables of the corpus.
+    Optimizer,
+    /// The tables of the corpus, because the amount of keys or the corpus file changed.
+    Tables,
+    /// Everything, starting from reading the corpus again.
+    Corpus,
+}
+
+/// Analyzes a layout again every time its file changes, until interrupted. The corpus and the
+/// optimizer's tables of it stay loaded: new weights, or a new keyboard with the same amount of
+/// keys, only reclassify and reweigh trigrams. The tables are only rebuilt when the amount of keys
+/// or the corpus changes. Files that fail to load are reported, and picked up again once fixed.
+pub(crate) fn analyze(
+    flags: &load::ProjectFlags,
+    path: &str,
+    format: Format,
+) -> Result<(), CliError> {
+    let mut watcher = Watcher::default();
+    let mut corpus = None;
+
+    eprintln!("watching for changes, press ctrl-c to stop");
+
+    loop {
+        let reload = match tables(flags, path, format, &mut corpus, &mut watcher) {
+            Ok(rebuild) => rebuild == Rebuild::Corpus,
+            Err(e) => {
+                eprintln!("error: {e}");
+                watcher.wait() == Change::Corpus
+            }
+        };
+
+        if reload {
+            corpus = None;
+        }
+    }
+}
+
+/// Builds the optimizer's tables of the corpus for the current amount of keys, and runs sessions
+/// with them until they no longer fit.
+fn tables(
+    flags: &load::ProjectFlags,
+    path: &str,
+    format: Format,
+    corpus: &mut Option<(PathBuf, Data)>,
+    watcher: &mut Watcher,
+) -> Result<Rebuild, CliError> {
+    let (project, keyboard, file) = setup(flags, path, watcher)?;
+
+    let corpus_path = project.corpus.clone().unwrap_or_default();
+    if corpus.as_ref().is_none_or(|(p, _)| *p != corpus_path) {
+        *corpus = Some((corpus_path, project.corpus()?));
+    }
+    let (corpus_path, data) = corpus.as_ref().expect("the corpus is loaded");
+
+    let types = TrigramTypes::with_defaults(keyboard.unwrap_or_else(|| file.keyboard()));
+    let base = Optimizer::new(&types, data, Weights::default());
+
+    loop {
+        match session(flags, path, format, &base, corpus_path, watcher) {
+            Ok(Rebuild::Optimizer) => {}
+            Ok(rebuild) => return Ok(rebuild),
+            Err(e) => {
+                eprintln!("error: {e}");
+
+                if watcher.wait() == Change::Corpus {
+                    return Ok(Rebuild::Corpus);
+                }
+            }
+        }
+    }
+}
+
+/// Loads the project and the layout, and starts watching their files. Also returns the keyboard
+/// of the project, if it has one.
+fn setup(
+    flags: &load::ProjectFlags,
+    path: &str,
+    watcher: &mut Watcher,
+) -> Result<(Project, Option<Keyboard>, LayoutFile), CliError> {
+    let project = load::project(flags);
+    watcher.track(project.as_ref().ok(), path);
+    let project = project?;
+
+    let keyboard = project.keyboard()?;
+    let file = LayoutFile::load(path)?;
+
+    Ok((project, keyboard, file))
+}
+
+/// Creates an optimizer from the tables of `base`, and analyzes the layout whenever it changes.
+/// Layouts or weights that fail to load are reported without leaving the session. Returns once
+/// the keyboard, project or corpus changes.
+fn session(
+    flags: &load::ProjectFlags,
+    path: &str,
+    format: Format,
+    base: &Optimizer,
+    corpus: &Path,
+    watcher: &mut Watcher,
+) -> Result<Rebuild, CliError> {
+    let (project, keyboard, file) = setup(flags, path, watcher)?;
+    let types = TrigramTypes::with_defaults(keyboard.clone().unwrap_or_else(|| file.keyboard()));
+
+    if project.corpus.as_deref().unwrap_or(Path::new("")) != corpus {
+        return Ok(Rebuild::Tables);
+    }
+
+    let Some(mut optimizer) = base.with_keyboard(&types, &weights(&project, &types)?) else {
+        return Ok(Rebuild::Tables);
+    };
+    let mut file = Some(file);
+
+    loop {
+        let file = match file.take() {
+            Some(file) => Ok(file),
+            None => LayoutFile::load(path),
+        };
+
+        match file {
+            // Without a keyboard of its own, the project uses the one of the layout, which may
+            // have been edited too.
+            Ok(file) if keyboard.is_none() && file.keyboard() != *optimizer.keyboard() => {
+                return Ok(Rebuild::Optimizer);
+            }
+            Ok(file) => match analyze::print(&optimizer, &file, format) {
+                Ok(()) if format == Format::Text => println!(),
+                Ok(()) => {}
+                Err(e) => eprintln!("error: {e}"),
+            },
+            Err(e) => eprintln!("error: {e}"),
+        }
+
+        match watcher.wait() {
+            Change::Layout => {}
+            Change::Weights => match weights(&project, &types) {
+                Ok(weights) => {
+                    optimizer = base
+                        .with_keyboard(&types, &weights)
+                        .expect("the amount of keys is the same");
+                }
+                Err(e) => eprintln!("error: {e}"),
+            },
+            Change::Keyboard | Change::Project => return Ok(Rebuild::Optimizer),
+            Change::Corpus => return Ok(Rebuild::Corpus),
+        }
+    }
+}
+
+/// Loads the weights of the project, and prints what's suspicious about them.
+fn weights(project: &Project, types: &TrigramTypes) -> Result<Weights, CliError> {
+    let weights = project.weights()?;
+    load::print_warnings(&weights.validate(types).map_err(ProjectError::from)?);
+
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a file with a modification time of `secs` after the epoch, so changes are noticed
+    /// even where timestamps are coarse.
+    fn write(path: &Path, contents: &str, secs: u64) {
+        std::fs::write(path, contents).unwrap();
+
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    /// Like [`Watcher::wait`], but gives up after a few seconds instead of hanging.
+    fn wait_timeout(watcher: &mut Watcher) -> Option<Change> {
+        (0..20).find_map(|_| {
+            std::thread::sleep(POLL);
+            watcher.changed()
+        })
+    }
+
+    #[test]
+    fn changes() {
+        let dir = std::env::temp_dir().join(format!("oxeygen-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let layout = dir.join("layout.txt");
+        let weights = dir.join("weights.toml");
+        write(&layout, "abc", 1);
+        write(&weights, "Sfb = -3", 1);
+
+        let project = Project {
+            weights: Some(Source::Path(weights.clone())),
+            ..Default::default()
+        };
+        let mut watcher = Watcher::default();
+        watcher.track(Some(&project), layout.to_str().unwrap());
+
+        write(&layout, "abd", 2);
+        assert_eq!(wait_timeout(&mut watcher), Some(Change::Layout));
+
+        write(&layout, "abe", 3);
+        write(&weights, "Sfb = -2", 3);
+        assert_eq!(wait_timeout(&mut watcher), Some(Change::Weights));
+        assert_eq!(watcher.changed(), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
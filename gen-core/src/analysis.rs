@@ -0,0 +1,59 @@
+//! Results of analyzing layouts and corpora, in a form that can be serialized for other tools.
+
+use serde::Serialize;
+
+/// Version of the schema the types in this module serialize to. It goes up whenever a field is
+/// removed, renamed or changes meaning, but not when fields are added.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything [`Optimizer::analyze`](crate::optimizer::Optimizer::analyze) knows about a layout.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayoutAnalysis {
+    pub name: String,
+    /// Keys of the layout row by row, written like in the text format of
+    /// [`Layout::parse`](crate::layout::Layout::parse).
+    pub rows: Vec<Vec<String>>,
+    pub score: f32,
+    /// Percentage of trigrams of each type, most common first.
+    pub trigram_types: Vec<Frequency>,
+    /// Percentage of key presses made by each finger, from the left pinky to the right pinky.
+    pub finger_usage: Vec<Frequency>,
+}
+
+impl LayoutAnalysis {
+    /// Percentage of trigrams of a type, or 0 if the layout has none of them.
+    pub fn trigram_type(&self, name: &str) -> f32 {
+        self.trigram_types
+            .iter()
+            .find(|f| f.name == name)
+            .map(|f| f.percentage)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frequency {
+    pub name: String,
+    pub percentage: f32,
+}
+
+/// How often an n-gram occurs in a corpus.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NgramFrequency {
+    pub ngram: String,
+    pub count: u64,
+    pub percentage: f64,
+}
+
+impl NgramFrequency {
+    pub fn new<const N: usize>(ngram: [char; N], count: u64, total: u64) -> Self {
+        Self {
+            ngram: ngram.iter().collect(),
+            count,
+            percentage: match total {
+                0 => 0.0,
+                total => count as f64 / total as f64 * 100.0,
+            },
+        }
+    }
+}
@@ -1,19 +1,22 @@
+use std::collections::VecDeque;
+
 use fxhash::FxHashMap;
 use sliding_window_alt::SlidingWindow;
 
-use crate::{REPEAT_KEY, REPLACEMENT_CHAR, SHIFT_CHAR};
+use crate::{refiner_config::RefinerConfig, REPEAT_KEY, REPLACEMENT_CHAR, SHIFT_CHAR};
 
 #[derive(Debug)]
 pub struct CorpusRefiner {
-    // multiple_char_rules: FxHashMap<usize, Vec<(Vec<char>, Vec<char>)>>,
-    longest_rule: usize,
+    multiple_char_rules: FxHashMap<Vec<char>, Vec<char>>,
+    pub(crate) longest_rule: usize,
     map: FxHashMap<char, Vec<char>>,
     repeat_key: bool,
     raw: bool,
+    pub(crate) config: Option<RefinerConfig>,
 }
 
 pub struct CorpusRefinerBuilder {
-    // multiple_char_rules: FxHashMap<usize, Vec<(Vec<char>, Vec<char>)>>,
+    multiple_char_rules: FxHashMap<Vec<char>, Vec<char>>,
     shift_to_replacement: bool,
     longest_rule: usize,
     map: FxHashMap<char, Vec<char>>,
@@ -25,7 +28,7 @@ pub struct RawCorpusRefiner;
 impl CorpusRefiner {
     pub fn builder() -> CorpusRefinerBuilder {
         CorpusRefinerBuilder {
-            // multiple_char_rules: FxHashMap::default(),
+            multiple_char_rules: FxHashMap::default(),
             shift_to_replacement: false,
             longest_rule: 1,
             map: FxHashMap::default(),
@@ -36,6 +39,83 @@ impl CorpusRefiner {
     pub fn raw() -> RawCorpusRefiner {
         RawCorpusRefiner
     }
+
+    /// The config this refiner was built from, if any.
+    pub fn config(&self) -> Option<&RefinerConfig> {
+        self.config.as_ref()
+    }
+
+    /// The state this refiner is in after refining `text` from the default state. Only looks as
+    /// far back as needed, so this is cheap for the end of a large piece of text.
+    ///
+    /// Multiple character rules are not taken into account, because where a match starts can
+    /// depend on the text long before it. With those, this is only a guess that's right as long as
+    /// no rule matches across the end of `text`.
+    pub fn state_after<I>(&self, text: I) -> RefinerState
+    where
+        I: IntoIterator<Item = char>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let mut rev = text.into_iter().rev().peekable();
+
+        let previous = match rev.peek() {
+            Some(&c) => c,
+            None => return RefinerState::default(),
+        };
+
+        // shift is only released or pressed by characters that are translated, and aren't
+        // turned into a repeat key
+        let mut shift_pressed = false;
+
+        while let Some(c) = rev.next() {
+            let before = rev.peek().copied().unwrap_or(REPLACEMENT_CHAR);
+
+            if self.repeat_key && c == before {
+                continue;
+            }
+
+            if let Some(to) = self.map.get(&c) {
+                shift_pressed = to[0] == SHIFT_CHAR;
+                break;
+            }
+        }
+
+        RefinerState {
+            previous,
+            shift_pressed,
+            skip: 0,
+        }
+    }
+
+    /// Whether the end of `text` is the start of a multiple character rule that continues past it.
+    pub(crate) fn may_continue(&self, text: &[char]) -> bool {
+        self.multiple_char_rules.keys().any(|from| {
+            (1..from.len().min(text.len() + 1)).any(|len| text[text.len() - len..] == from[..len])
+        })
+    }
+}
+
+/// Everything a refiner remembers between characters. Refining text in parts gives the same
+/// result as refining it at once, as long as each part starts from the state the previous part
+/// ended in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefinerState {
+    /// The last character that was refined.
+    pub previous: char,
+    pub shift_pressed: bool,
+    /// Amount of characters at the start of the next part that were already refined, because a
+    /// multiple character rule matched across the end of the previous part.
+    pub skip: usize,
+}
+
+impl Default for RefinerState {
+    fn default() -> Self {
+        Self {
+            previous: REPLACEMENT_CHAR,
+            shift_pressed: false,
+            skip: 0,
+        }
+    }
 }
 
 impl CorpusRefinerBuilder {
@@ -159,25 +239,69 @@ impl CorpusRefinerBuilder {
         .with_uppercase([('\'', '«'), ('\'', '»'), ('\'', '“'), ('\'', '”')], false)
     }
 
-    // pub fn multiple_char_rule(&mut self, from: &str, to: &str) -> &mut Self {
-    //     self.longest_rule = self.longest_rule.max(from.len());
+    /// Translates a sequence of characters, like `"qu"` to a single magic key or `"\r\n"` to
+    /// `"\n"`. When sequences overlap the longest one that matches is used, and sequences take
+    /// priority over single characters and the repeat key. Translations starting with the shift
+    /// character hold shift the same way uppercase letters do. An empty `from` is ignored.
+    ///
+    /// With `include_uppercase_versions`, the capitalized and fully uppercase versions of `from`
+    /// are translated to `to` with shift in front.
+    pub fn multiple_char_rule(
+        &mut self,
+        from: &str,
+        to: &str,
+        include_uppercase_versions: bool,
+    ) -> &mut Self {
+        let to = to.chars().collect::<Vec<_>>();
 
-    //     self.multiple_char_rules
-    //         .entry(from.len())
-    //         .and_modify(|e| e.push((from.chars().collect(), to.chars().collect())))
-    //         .or_default();
-    //     self
-    // }
+        let mut rules = vec![(from.to_owned(), to.clone())];
+
+        if include_uppercase_versions {
+            let mut chars = from.chars();
+            let capitalized = chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default();
+
+            let shifted = [SHIFT_CHAR].into_iter().chain(to).collect::<Vec<_>>();
+
+            for upper in [capitalized, from.to_uppercase()] {
+                if upper != from {
+                    rules.push((upper, shifted.clone()));
+                }
+            }
+        }
+
+        for (from, to) in rules {
+            let from = from.chars().collect::<Vec<_>>();
+
+            match from.len() {
+                0 => {}
+                1 => {
+                    self.map.insert(from[0], to);
+                }
+                len => {
+                    self.longest_rule = self.longest_rule.max(len);
+                    self.multiple_char_rules.insert(from, to);
+                }
+            }
+        }
+        self
+    }
 
     pub fn repeat_key(&mut self, enable: bool) -> &mut Self {
         self.repeat_key = enable;
-        self.longest_rule = self.longest_rule.max(2);
         self
     }
 
     pub fn build(&mut self) -> CorpusRefiner {
         if self.shift_to_replacement {
-            for translation in self.map.values_mut() {
+            let translations = self
+                .map
+                .values_mut()
+                .chain(self.multiple_char_rules.values_mut());
+
+            for translation in translations {
                 for c in translation {
                     if *c == SHIFT_CHAR {
                         *c = REPLACEMENT_CHAR;
@@ -187,11 +311,12 @@ impl CorpusRefinerBuilder {
         }
 
         CorpusRefiner {
-            // multiple_char_rules: self.multiple_char_rules,
+            multiple_char_rules: std::mem::take(&mut self.multiple_char_rules),
             longest_rule: self.longest_rule,
             map: std::mem::take(&mut self.map),
             repeat_key: self.repeat_key,
             raw: false,
+            config: None,
         }
     }
 }
@@ -202,7 +327,89 @@ pub struct CorpusRefinerIterator<'a, I> {
     refiner: &'a CorpusRefiner,
     iter: I,
     window: SlidingWindow<char>,
+    lookahead: VecDeque<char>,
     is_shift_pressed: bool,
+    skip: usize,
+    consumed: usize,
+    unmapped: FxHashMap<char, u64>,
+}
+
+impl<'a, I> CorpusRefinerIterator<'a, I>
+where
+    I: Iterator<Item = char>,
+{
+    fn new(refiner: &'a CorpusRefiner, iter: I, state: RefinerState) -> Self {
+        let mut window = SlidingWindow::new(2, REPLACEMENT_CHAR);
+        window.push(state.previous);
+
+        Self {
+            refiner,
+            iter,
+            window,
+            lookahead: VecDeque::with_capacity(refiner.longest_rule),
+            is_shift_pressed: state.shift_pressed,
+            skip: state.skip,
+            consumed: 0,
+            unmapped: FxHashMap::default(),
+        }
+    }
+
+    /// The state to continue refining from after everything consumed so far.
+    pub fn state(&self) -> RefinerState {
+        RefinerState {
+            previous: self.window[0],
+            shift_pressed: self.is_shift_pressed,
+            skip: self.skip,
+        }
+    }
+
+    /// Amount of characters consumed so far, including skipped ones.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// How often each character without a translation was seen so far.
+    pub fn unmapped(&self) -> &FxHashMap<char, u64> {
+        &self.unmapped
+    }
+
+    fn consume(&mut self, len: usize) {
+        for c in self.lookahead.drain(..len) {
+            self.window.push(c);
+        }
+        self.consumed += len;
+    }
+
+    fn press(&mut self, to: &[char]) -> Vec<char> {
+        match to.first() {
+            Some(&SHIFT_CHAR) if self.is_shift_pressed => to[1..].to_vec(),
+            Some(&SHIFT_CHAR) => {
+                self.is_shift_pressed = true;
+                to.to_vec()
+            }
+            _ => {
+                self.is_shift_pressed = false;
+                to.to_vec()
+            }
+        }
+    }
+
+    fn matching_rule(&mut self) -> Option<(usize, &'a [char])> {
+        let refiner = self.refiner;
+
+        if refiner.multiple_char_rules.is_empty() {
+            return None;
+        }
+
+        let ahead = self.lookahead.make_contiguous();
+
+        (2..=ahead.len()).rev().find_map(|len| {
+            refiner
+                .multiple_char_rules
+                .get(&ahead[..len])
+                .map(|to| (len, to.as_slice()))
+        })
+    }
 }
 
 impl<'a, I> Iterator for CorpusRefinerIterator<'a, I>
@@ -212,28 +419,39 @@ where
     type Item = Vec<char>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let c = self.iter.next()?;
+        while self.lookahead.len() < self.refiner.longest_rule.max(1) + self.skip {
+            match self.iter.next() {
+                Some(c) => self.lookahead.push_back(c),
+                None => break,
+            }
+        }
+
+        if self.skip > 0 {
+            let skip = self.skip.min(self.lookahead.len());
+            self.consume(skip);
+            self.skip -= skip;
+        }
+
+        let c = *self.lookahead.front()?;
+
         if self.refiner.raw {
+            self.consume(1);
             return Some(vec![c]);
         }
 
-        self.window.push(c);
+        if let Some((len, to)) = self.matching_rule() {
+            self.consume(len);
+            return Some(self.press(to));
+        }
+
+        self.consume(1);
 
         if self.refiner.repeat_key && self.window[0] == self.window[1] {
             Some(vec![REPEAT_KEY])
         } else if let Some(to) = self.refiner.map.get(&c) {
-            if to[0] == SHIFT_CHAR {
-                if self.is_shift_pressed {
-                    Some(to[1..].to_vec())
-                } else {
-                    self.is_shift_pressed = true;
-                    Some(to.clone())
-                }
-            } else {
-                self.is_shift_pressed = false;
-                Some(to.clone())
-            }
+            Some(self.press(to))
         } else {
+            *self.unmapped.entry(c).or_insert(0) += 1;
             Some(vec![REPLACEMENT_CHAR])
         }
     }
@@ -248,15 +466,23 @@ pub trait RefineCorpus: Iterator {
         Self: Iterator<Item = char>,
         Self: Sized,
     {
-        let window = SlidingWindow::new(refiner.longest_rule, REPLACEMENT_CHAR);
         let iter = self.chain([REPLACEMENT_CHAR, REPLACEMENT_CHAR]);
 
-        CorpusRefinerIterator {
-            refiner,
-            iter,
-            window,
-            is_shift_pressed: false,
-        }
+        CorpusRefinerIterator::new(refiner, iter, RefinerState::default())
+    }
+
+    /// Refines a part of a larger piece of text, continuing from `state`. Unlike
+    /// [`RefineCorpus::refine`], nothing is appended to the end.
+    fn refine_from(
+        self,
+        refiner: &CorpusRefiner,
+        state: RefinerState,
+    ) -> CorpusRefinerIterator<'_, Self>
+    where
+        Self: Iterator<Item = char>,
+        Self: Sized,
+    {
+        CorpusRefinerIterator::new(refiner, self, state)
     }
 }
 
@@ -285,4 +511,41 @@ mod tests {
 
         println!("{translation}");
     }
+
+    #[test]
+    fn multiple_char_rules() {
+        let refiner = CorpusRefiner::builder()
+            .include("abcdefghijklmnopqrstuvwxyz".chars(), true)
+            .include_space()
+            .repeat_key(true)
+            .multiple_char_rule("qu", "*", true)
+            .multiple_char_rule("que", "%", false)
+            .multiple_char_rule("ll", "L", false)
+            .multiple_char_rule("\r\n", "\n", false)
+            .multiple_char_rule("ß", "ss", false)
+            .build();
+
+        let refine = |s: &str| s.chars().refine(&refiner).flatten().collect::<String>();
+
+        assert_eq!(refine("quit queue"), "*it %ue�@");
+        assert_eq!(refine("Quit QUIT"), "⇑*it ⇑*it�@");
+        assert_eq!(refine("small\r\nbeer"), "smaL\nbe@r�@");
+        assert_eq!(refine("Straße"), "⇑strasse�@");
+    }
+
+    #[test]
+    fn state_after() {
+        let refiner = CorpusRefiner::builder()
+            .include("abc".chars(), true)
+            .repeat_key(true)
+            .build();
+
+        let state = refiner.state_after("xABBx?".chars());
+        assert_eq!(state.previous, '?');
+        assert!(state.shift_pressed);
+
+        let state = refiner.state_after("AB b".chars());
+        assert!(!state.shift_pressed);
+        assert_eq!(refiner.state_after("".chars()), RefinerState::default());
+    }
 }
@@ -0,0 +1,98 @@
+use fxhash::FxHashMap;
+use serde::{Serialize, Serializer};
+
+/// Statistics about how a text was turned into trigrams, to check how well a refiner fits a
+/// corpus.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CorpusStats {
+    /// Amount of files that were counted.
+    pub files: u64,
+    /// Files in a folder that were skipped because they couldn't be read as UTF-8 text.
+    pub skipped_files: Vec<String>,
+    /// Amount of characters in the original text.
+    pub chars: u64,
+    /// Characters the refiner has no translation for, and how often they were seen. They're
+    /// serialized most common first.
+    #[serde(serialize_with = "serialize_unmapped")]
+    pub unmapped: FxHashMap<char, u64>,
+    /// Amount of trigrams that were counted.
+    pub trigrams: u64,
+    /// Amount of trigrams that weren't counted because they contained an unmapped character.
+    pub discarded_trigrams: u64,
+}
+
+impl CorpusStats {
+    pub fn unmapped_chars(&self) -> u64 {
+        self.unmapped.values().sum()
+    }
+
+    /// Fraction of the original characters that had no translation.
+    pub fn unmapped_fraction(&self) -> f64 {
+        fraction(self.unmapped_chars(), self.chars)
+    }
+
+    /// Fraction of all trigrams that were discarded.
+    pub fn discarded_fraction(&self) -> f64 {
+        fraction(
+            self.discarded_trigrams,
+            self.trigrams + self.discarded_trigrams,
+        )
+    }
+
+    /// The `n` most common unmapped characters, most common first.
+    pub fn top_unmapped(&self, n: usize) -> Vec<(char, u64)> {
+        let mut unmapped = sorted_unmapped(&self.unmapped);
+        unmapped.truncate(n);
+        unmapped
+    }
+}
+
+fn sorted_unmapped(unmapped: &FxHashMap<char, u64>) -> Vec<(char, u64)> {
+    let mut unmapped = unmapped
+        .iter()
+        .map(|(&c, &count)| (c, count))
+        .collect::<Vec<_>>();
+
+    unmapped.sort_by(|(c1, n1), (c2, n2)| n2.cmp(n1).then(c1.cmp(c2)));
+    unmapped
+}
+
+fn serialize_unmapped<S: Serializer>(
+    unmapped: &FxHashMap<char, u64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(sorted_unmapped(unmapped))
+}
+
+fn fraction(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => part as f64 / total as f64,
+    }
+}
+
+impl std::ops::Add for CorpusStats {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        for (c, count) in rhs.unmapped {
+            *self.unmapped.entry(c).or_insert(0) += count;
+        }
+        self.files += rhs.files;
+        self.skipped_files.extend(rhs.skipped_files);
+        self.chars += rhs.chars;
+        self.trigrams += rhs.trigrams;
+        self.discarded_trigrams += rhs.discarded_trigrams;
+
+        self
+    }
+}
+
+/// How far along counting the files of a corpus is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CorpusProgress {
+    pub files: usize,
+    pub total_files: usize,
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
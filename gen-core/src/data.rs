@@ -3,7 +3,13 @@ use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, serde_conv};
 use thiserror::Error;
 
-use crate::{corpus_refiner::CorpusRefinerIterator, REPLACEMENT_CHAR};
+use crate::{
+    corpus_refiner::{CorpusRefiner, CorpusRefinerIterator, RefineCorpus, RefinerState},
+    corpus_stats::{CorpusProgress, CorpusStats},
+    keystrokes::{key_presses, KeystrokeOptions},
+    refiner_config::RefinerConfig,
+    OTHER_CHAR, REPLACEMENT_CHAR,
+};
 
 #[cfg(not(target_arch = "wasm32"))]
 mod exclude_wasm {
@@ -17,7 +23,7 @@ mod exclude_wasm {
     pub use rayon::prelude::*;
     pub use serde_json::ser::PrettyFormatter;
 
-    pub use crate::corpus_refiner::{CorpusRefiner, RefineCorpus};
+    pub use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
     pub const TWO_MB: usize = 2 * 1024 * 1024;
 }
@@ -43,12 +49,125 @@ serde_conv!(
 
 type FxIndexMap<K, V> = IndexMap<K, V, fxhash::FxBuildHasher>;
 
+/// Amount of trigrams a corpus that only has relative frequencies is assumed to contain, so it
+/// can be converted to counts. This keeps about as much precision as the `f32` frequencies had.
+pub const LEGACY_TOTAL: u64 = 1_000_000_000;
+
+/// Trigram counts of a corpus. Percentages are derived from the counts when needed, which means
+/// merging corpora weighs each one by the amount of text it came from.
 #[serde_as]
 #[derive(Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "DataRepr")]
 pub struct Data {
     pub(crate) name: String,
+    #[serde(default, skip_serializing_if = "DataMeta::is_empty")]
+    pub(crate) meta: DataMeta,
+    pub(crate) total: u64,
     #[serde_as(as = "FxIndexMap<TrigramAsStr, _>")]
-    pub(crate) trigrams: FxIndexMap<[char; 3], f32>,
+    pub(crate) trigrams: FxIndexMap<[char; 3], u64>,
+}
+
+/// Describes how a corpus was made, so it can be rebuilt later.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DataMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blend: Option<Vec<BlendSource>>,
+    /// The refiner the corpus was cleaned with, if it was built from a config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refiner: Option<RefinerConfig>,
+}
+
+impl DataMeta {
+    pub fn is_empty(&self) -> bool {
+        self.blend.is_none() && self.refiner.is_none()
+    }
+}
+
+/// A corpus that was blended into another one, with its share of the result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlendSource {
+    pub name: String,
+    pub weight: f64,
+}
+
+/// What pruning removed from a corpus. Percentages of the pruned corpus are renormalized, so they
+/// add up to 100% again, which means every remaining trigram weighs slightly more than before.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PruneReport {
+    pub trigrams_before: usize,
+    pub trigrams_after: usize,
+    /// Total count of the corpus before pruning.
+    pub total_before: u64,
+    /// Count of all trigrams that were removed.
+    pub discarded: u64,
+    /// Characters that were folded into [`OTHER_CHAR`].
+    pub folded: Vec<char>,
+}
+
+impl PruneReport {
+    /// Fraction of the corpus that was removed.
+    pub fn discarded_fraction(&self) -> f64 {
+        match self.total_before {
+            0 => 0.0,
+            total => self.discarded as f64 / total as f64,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DataRepr {
+    Counts {
+        name: String,
+        #[serde(default)]
+        meta: Box<DataMeta>,
+        total: u64,
+        #[serde_as(as = "FxIndexMap<TrigramAsStr, _>")]
+        trigrams: FxIndexMap<[char; 3], u64>,
+    },
+    Legacy {
+        name: String,
+        #[serde_as(as = "FxIndexMap<TrigramAsStr, _>")]
+        trigrams: FxIndexMap<[char; 3], f64>,
+    },
+}
+
+impl From<DataRepr> for Data {
+    fn from(repr: DataRepr) -> Self {
+        match repr {
+            DataRepr::Counts {
+                name,
+                meta,
+                total,
+                trigrams,
+            } => Self {
+                name,
+                meta: *meta,
+                total,
+                trigrams,
+            },
+            // Files without a total either hold plain counts, or frequencies that are converted
+            // to counts out of `LEGACY_TOTAL`.
+            DataRepr::Legacy { name, trigrams } => {
+                let sum = trigrams.values().sum::<f64>();
+                let percentages = (sum - 100.0).abs() <= 1.0;
+                let counts = !percentages && trigrams.values().all(|f| f.fract() == 0.0);
+
+                let scale = match counts {
+                    true => 1.0,
+                    false => LEGACY_TOTAL as f64 / sum,
+                };
+
+                let trigrams = trigrams
+                    .into_iter()
+                    .map(|(t, f)| (t, (f * scale).round() as u64))
+                    .collect();
+
+                Self::new(trigrams, &name)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -77,38 +196,62 @@ pub enum DataError {
     #[error("Specifying a name for the corpus is required")]
     NamelessDataError,
 
+    #[error("Line {0} of the keystroke log: {1}")]
+    KeystrokeError(usize, String),
+
+    #[error("Blending requires at least one corpus")]
+    EmptyBlendError,
+
+    #[error("Couldn't read '{path}': {source}")]
+    ReadError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("'{path}' is not a valid corpus: {source}")]
+    ParseError {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("Blend weight for '{0}' should be a positive number, found {1}")]
+    BlendWeightError(String, f64),
+
     #[cfg(target_arch = "wasm32")]
     #[error("{0}")]
     GlooError(#[from] gloo_net::Error),
 }
 
-impl FromIterator<char> for Data {
-    fn from_iter<T: IntoIterator<Item = char>>(iter: T) -> Self {
-        let mut res = Self::default();
-        let mut iter = iter.into_iter();
-
-        if let Some(mut c1) = iter.next() {
-            if let Some(mut c2) = iter.next() {
-                for c3 in iter {
-                    if c1 == REPLACEMENT_CHAR || c2 == REPLACEMENT_CHAR || c3 == REPLACEMENT_CHAR {
-                        continue;
-                    }
-
-                    res.trigrams
-                        .entry([c1, c2, c3])
-                        .and_modify(|e| *e += 1.0)
-                        .or_insert(1.0);
-
-                    c1 = c2;
-                    c2 = c3;
-                }
-            }
+/// Refines `trigram` after `previous` and followed by `next`. Returns the output from the first
+/// character of the trigram onwards, and how many of those characters it turned into, or `None`
+/// if it was part of a rule that started at `previous`.
+fn refine_trigram(
+    refiner: &CorpusRefiner,
+    previous: Option<char>,
+    trigram: &[char; 3],
+    next: Option<char>,
+) -> Option<(Vec<char>, usize)> {
+    let chars = previous.into_iter().chain(*trigram).chain(next);
+    let mut iter = chars.refine_from(refiner, RefinerState::default());
+
+    if previous.is_some() {
+        iter.next();
+
+        if iter.consumed() > 1 {
+            return None;
         }
+    }
 
-        let total = res.trigrams.values().sum::<f32>();
-        res.trigrams.values_mut().for_each(|v| *v /= total / 100.0);
+    let mut output = iter.next()?;
+    let first = output.len();
+    output.extend(iter.flatten());
 
-        res
+    Some((output, first))
+}
+
+impl FromIterator<char> for Data {
+    fn from_iter<T: IntoIterator<Item = char>>(iter: T) -> Self {
+        Self::count_trigrams(iter).0
     }
 }
 
@@ -131,66 +274,410 @@ impl std::ops::Add for Data {
     type Output = Self;
 
     fn add(mut self, rhs: Self) -> Self::Output {
-        for (trigram, freq) in rhs.trigrams.into_iter() {
-            self.trigrams
-                .entry(trigram)
-                .and_modify(|f| *f = (*f + freq) / 2.0)
-                .or_insert(freq);
+        for (trigram, count) in rhs.trigrams.into_iter() {
+            *self.trigrams.entry(trigram).or_insert(0) += count;
         }
+        self.total += rhs.total;
 
         self
     }
 }
 
 impl Data {
-    pub fn new(trigrams: FxIndexMap<[char; 3], f32>, name: &str) -> Self {
+    /// Creates data from trigram counts, where the total is the sum of all counts.
+    pub fn new(trigrams: FxIndexMap<[char; 3], u64>, name: &str) -> Self {
         Self {
+            total: trigrams.values().sum(),
             trigrams,
             name: name.into(),
+            meta: DataMeta::default(),
+        }
+    }
+
+    /// Blends several corpora into a new one, where each corpus makes up its share of the
+    /// result regardless of its size. Weights don't need to add up to anything, so `60, 25, 15`
+    /// works just as well as `0.6, 0.25, 0.15`. The result is as large as all sources combined,
+    /// and records the recipe in its metadata. Empty sources are left out.
+    pub fn blend(sources: &[(&Data, f64)], name: &str) -> Result<Self, DataError> {
+        if sources.is_empty() {
+            return Err(DataError::EmptyBlendError);
+        }
+
+        if let Some((data, weight)) = sources.iter().find(|(_, w)| !(*w > 0.0 && w.is_finite())) {
+            return Err(DataError::BlendWeightError(data.name.clone(), *weight));
+        }
+
+        // Empty corpora have nothing to contribute, so their weight would only dilute the others.
+        let sources = sources
+            .iter()
+            .filter(|(d, _)| d.total > 0)
+            .collect::<Vec<_>>();
+
+        let weight_sum = sources.iter().map(|(_, w)| w).sum::<f64>();
+        let total = sources.iter().map(|(d, _)| d.total).sum::<u64>();
+
+        let mut shares = FxIndexMap::<[char; 3], f64>::default();
+
+        for (data, weight) in &sources {
+            let scale = weight / weight_sum / data.total as f64;
+
+            for (&trigram, &count) in data.trigrams.iter() {
+                *shares.entry(trigram).or_insert(0.0) += count as f64 * scale;
+            }
+        }
+
+        let trigrams = shares
+            .into_iter()
+            .map(|(t, share)| (t, (share * total as f64).round() as u64))
+            .collect();
+
+        let mut res = Self::new(trigrams, name).sorted();
+        res.meta.blend = Some(
+            sources
+                .iter()
+                .map(|(data, weight)| BlendSource {
+                    name: data.name.clone(),
+                    weight: weight / weight_sum,
+                })
+                .collect(),
+        );
+
+        Ok(res)
+    }
+
+    /// Counts the trigrams of physical key presses in a keystroke log. See
+    /// [`keystrokes`](crate::keystrokes) for the format.
+    pub fn from_keystrokes(
+        log: &str,
+        name: &str,
+        options: &KeystrokeOptions,
+    ) -> Result<Self, DataError> {
+        let presses =
+            key_presses(log, options).map_err(|(l, e)| DataError::KeystrokeError(l, e))?;
+
+        let mut res = Self::from_iter(presses).sorted();
+        res.name = name.into();
+
+        Ok(res)
+    }
+
+    /// Counts the trigrams of `iter`, and returns how many were discarded because they contain
+    /// characters that were filtered out.
+    fn count_trigrams<T: IntoIterator<Item = char>>(iter: T) -> (Self, u64) {
+        let mut res = Self::default();
+        let mut discarded = 0;
+        let mut iter = iter.into_iter();
+
+        if let Some(mut c1) = iter.next() {
+            if let Some(mut c2) = iter.next() {
+                for c3 in iter {
+                    if !res.add_trigram([c1, c2, c3]) {
+                        discarded += 1;
+                    }
+
+                    c1 = c2;
+                    c2 = c3;
+                }
+            }
         }
+
+        (res, discarded)
+    }
+
+    /// Counts a trigram, unless it contains characters that were filtered out. Returns whether
+    /// it was counted.
+    fn add_trigram(&mut self, trigram: [char; 3]) -> bool {
+        if trigram.contains(&REPLACEMENT_CHAR) {
+            return false;
+        }
+
+        *self.trigrams.entry(trigram).or_insert(0) += 1;
+        self.total += 1;
+        true
+    }
+
+    /// The `n` most common n-grams of length `N`, which can be 1, 2 or 3, with their counts. Single
+    /// characters and bigrams are counted from the start of every trigram.
+    pub fn top_ngrams<const N: usize>(&self, n: usize) -> Vec<([char; N], u64)> {
+        assert!(
+            (1..=3).contains(&N),
+            "n-grams should be 1 to 3 characters long"
+        );
+
+        let mut ngrams = FxIndexMap::<[char; N], u64>::default();
+
+        for (trigram, &count) in self.trigrams.iter() {
+            let ngram = trigram[..N].try_into().expect("N is at most 3");
+            *ngrams.entry(ngram).or_insert(0) += count;
+        }
+
+        ngrams.sort_by(|t1, c1, t2, c2| c2.cmp(c1).then_with(|| t1.cmp(t2)));
+
+        ngrams.into_iter().take(n).collect()
     }
 
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    pub fn inner(&self) -> &FxIndexMap<[char; 3], f32> {
+    pub fn renamed(mut self, name: &str) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn meta(&self) -> &DataMeta {
+        &self.meta
+    }
+
+    /// The total amount of trigrams in the corpus.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, trigram: &[char; 3]) -> u64 {
+        self.trigrams.get(trigram).copied().unwrap_or_default()
+    }
+
+    /// Percentage of the corpus made up by `trigram`.
+    pub fn frequency(&self, trigram: &[char; 3]) -> f32 {
+        self.percentage(self.count(trigram))
+    }
+
+    /// Every trigram with its percentage of the corpus.
+    pub fn frequencies(&self) -> impl Iterator<Item = (&[char; 3], f32)> + '_ {
+        self.trigrams.iter().map(|(t, &c)| (t, self.percentage(c)))
+    }
+
+    fn percentage(&self, count: u64) -> f32 {
+        match self.total {
+            0 => 0.0,
+            total => (count as f64 * 100.0 / total as f64) as f32,
+        }
+    }
+
+    pub fn inner(&self) -> &FxIndexMap<[char; 3], u64> {
         &self.trigrams
     }
 
-    pub fn into_inner(self) -> FxIndexMap<[char; 3], f32> {
+    pub fn into_inner(self) -> FxIndexMap<[char; 3], u64> {
         self.trigrams
     }
 
     pub fn sorted(mut self) -> Self {
-        self.trigrams.sort_by(|t1, f1, t2, f2| {
-            let ord = f2
-                .partial_cmp(f1)
-                .expect("Values in the json should never be NaN");
+        self.trigrams
+            .sort_by(|t1, c1, t2, c2| c2.cmp(c1).then_with(|| t1.cmp(t2)));
 
-            if ord == std::cmp::Ordering::Equal {
-                t1.cmp(t2)
-            } else {
-                ord
+        self
+    }
+
+    /// Removes every trigram that makes up less than `min_percentage` of the corpus.
+    pub fn prune_below(&mut self, min_percentage: f64) -> PruneReport {
+        let total = self.total as f64;
+        self.prune(|_, count| count as f64 * 100.0 / total >= min_percentage)
+    }
+
+    /// Keeps only the `k` most common trigrams.
+    pub fn keep_top(&mut self, k: usize) -> PruneReport {
+        self.sort();
+        self.prune(|i, _| i < k)
+    }
+
+    /// Keeps the fewest, most common trigrams that together make up at least `percentage` of the
+    /// corpus.
+    pub fn keep_mass(&mut self, percentage: f64) -> PruneReport {
+        self.sort();
+
+        let target = self.total as f64 * percentage / 100.0;
+        let mut kept = 0;
+        let mut cumulative = 0;
+
+        for &count in self.trigrams.values() {
+            if cumulative as f64 >= target {
+                break;
             }
-        });
+            cumulative += count;
+            kept += 1;
+        }
 
-        let sum = self.trigrams.iter().map(|(_, &f)| f).sum::<f32>();
+        self.prune(|i, _| i < kept)
+    }
 
-        self.trigrams
-            .iter_mut()
-            .for_each(|(_, f)| *f = *f * 100.0 / sum);
+    /// Replaces every character except the `keep` most common ones with [`OTHER_CHAR`], merging
+    /// the trigrams that become equal. Nothing is removed, but the optimizer has far fewer
+    /// characters to consider.
+    pub fn fold_rare_chars(&mut self, keep: usize) -> PruneReport {
+        let mut chars = FxIndexMap::<char, u64>::default();
 
-        self
+        for (trigram, &count) in self.trigrams.iter() {
+            for &c in trigram {
+                *chars.entry(c).or_insert(0) += count;
+            }
+        }
+
+        chars.sort_by(|c1, n1, c2, n2| n2.cmp(n1).then_with(|| c1.cmp(c2)));
+
+        let folded = chars.into_keys().skip(keep).collect::<Vec<_>>();
+
+        let trigrams_before = self.trigrams.len();
+
+        if !folded.is_empty() {
+            let fold = |c: char| match folded.contains(&c) {
+                true => OTHER_CHAR,
+                false => c,
+            };
+
+            let mut trigrams = FxIndexMap::default();
+
+            for ([c1, c2, c3], count) in std::mem::take(&mut self.trigrams) {
+                *trigrams.entry([fold(c1), fold(c2), fold(c3)]).or_insert(0) += count;
+            }
+
+            self.trigrams = trigrams;
+            self.sort();
+        }
+
+        PruneReport {
+            trigrams_before,
+            trigrams_after: self.trigrams.len(),
+            total_before: self.total,
+            discarded: 0,
+            folded,
+        }
+    }
+
+    /// Applies `refiner` to a corpus that was already counted, for when the original text isn't
+    /// available. A trigram only shows a bit of the text it came from, so this is an
+    /// approximation:
+    ///
+    /// - Each trigram is refined after every character that comes before it in other trigrams,
+    ///   split by how often it does. This keeps shift presses and repeats right, and drops the
+    ///   trigrams that start halfway through a multiple character rule. Where the trigram was
+    ///   preceded by a filtered out character isn't known, so that's left out of the split.
+    /// - When a character turns into several, like with dead keys, every new trigram it starts is
+    ///   counted as often as the trigram it came from.
+    /// - When that needs more text than the trigram has, because characters were contracted or a
+    ///   rule continues past its end, the trigram is extended by one character in the same way.
+    ///   Rules that need even more than that are missed.
+    ///
+    /// Counts are rounded afterwards, so the total can change slightly.
+    pub fn refine(&self, refiner: &CorpusRefiner) -> Self {
+        type Neighbours = FxIndexMap<[char; 2], Vec<(char, u64)>>;
+
+        let mut before = Neighbours::default();
+        let mut after = Neighbours::default();
+
+        for (&[c1, c2, c3], &count) in self.trigrams.iter() {
+            before.entry([c2, c3]).or_default().push((c1, count));
+            after.entry([c1, c2]).or_default().push((c3, count));
+        }
+
+        // spreads `weight` over the neighbours of a pair of characters
+        let split = |neighbours: &Neighbours, pair: [char; 2], weight: f64| {
+            let neighbours = neighbours.get(&pair).map(Vec::as_slice).unwrap_or_default();
+            let total = neighbours.iter().map(|&(_, n)| n).sum::<u64>() as f64;
+
+            neighbours
+                .iter()
+                .map(move |&(c, n)| (Some(c), weight * n as f64 / total))
+                .collect::<Vec<_>>()
+        };
+
+        let mut counts = FxIndexMap::<[char; 3], f64>::default();
+        let mut add = |output: &[char], first: usize, weight: f64| {
+            for window in output.windows(3).take(first) {
+                if !window.contains(&REPLACEMENT_CHAR) {
+                    let trigram = [window[0], window[1], window[2]];
+                    *counts.entry(trigram).or_insert(0.0) += weight;
+                }
+            }
+        };
+
+        for (&trigram @ [c1, c2, c3], &count) in self.trigrams.iter() {
+            let mut contexts = split(&before, [c1, c2], count as f64);
+            if contexts.is_empty() {
+                contexts.push((None, count as f64));
+            }
+
+            for (previous, weight) in contexts {
+                let Some((output, first)) = refine_trigram(refiner, previous, &trigram, None)
+                else {
+                    continue;
+                };
+
+                let extend = output.len() < first + 2 || refiner.may_continue(&trigram);
+                let next = match extend {
+                    true => split(&after, [c2, c3], weight),
+                    false => Vec::new(),
+                };
+
+                if next.is_empty() {
+                    add(&output, first, weight);
+                }
+
+                for (next, weight) in next {
+                    if let Some((output, first)) = refine_trigram(refiner, previous, &trigram, next)
+                    {
+                        add(&output, first, weight);
+                    }
+                }
+            }
+        }
+
+        let trigrams = counts
+            .into_iter()
+            .map(|(t, count)| (t, count.round() as u64))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+
+        let mut res = Self::new(trigrams, &self.name).sorted();
+        res.meta.blend = self.meta.blend.clone();
+        res.meta.refiner = refiner.config().cloned();
+
+        res
+    }
+
+    /// Keeps the trigrams for which `keep` returns true, given their index and count, and
+    /// renormalizes the total to what's left.
+    fn prune(&mut self, keep: impl Fn(usize, u64) -> bool) -> PruneReport {
+        let trigrams_before = self.trigrams.len();
+        let total_before = self.total;
+
+        let mut i = 0;
+        self.trigrams.retain(|_, &mut count| {
+            i += 1;
+            keep(i - 1, count)
+        });
+        self.total = self.trigrams.values().sum();
+
+        PruneReport {
+            trigrams_before,
+            trigrams_after: self.trigrams.len(),
+            total_before,
+            discarded: total_before - self.total,
+            folded: Vec::new(),
+        }
+    }
+
+    fn sort(&mut self) {
+        self.trigrams
+            .sort_by(|t1, c1, t2, c2| c2.cmp(c1).then_with(|| t1.cmp(t2)));
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 impl Data {
+    /// Reads a corpus .json file. Errors name the file, and the line and column for bad json.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DataError> {
-        let content = std::fs::read_to_string(path)?;
-        let data = serde_json::from_str(&content)?;
-        Ok(data)
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|source| DataError::ReadError {
+            path: path.display().to_string(),
+            source,
+        })?;
+
+        serde_json::from_str(&content).map_err(|source| DataError::ParseError {
+            path: path.display().to_string(),
+            source,
+        })
     }
 
     pub fn from_path<P: AsRef<Path>>(
@@ -198,31 +685,146 @@ impl Data {
         name: &str,
         refiner: &CorpusRefiner,
     ) -> Result<Self, DataError> {
-        if path.as_ref().is_file() {
-            let f = std::fs::File::open(path)?;
-            Self::from_file(f, name, refiner)
-        } else if path.as_ref().is_dir() {
-            let mut new = std::fs::read_dir(path)?
-                .par_bridge()
-                .flatten()
-                .filter(|entry| entry.path().is_file())
-                .map(|entry| {
-                    let f = std::fs::File::open(entry.path())?;
-                    Self::from_file(f, name, refiner)
-                })
-                .flatten()
-                .reduce(|| Self::default(), |a, b| a + b)
-                .sorted();
+        Self::from_path_with_stats(path, name, refiner).map(|(data, _)| data)
+    }
 
-            new.name = name.to_string();
+    /// Like [`Data::from_path`], but also returns statistics about what the refiner filtered out.
+    pub fn from_path_with_stats<P: AsRef<Path>>(
+        path: P,
+        name: &str,
+        refiner: &CorpusRefiner,
+    ) -> Result<(Self, CorpusStats), DataError> {
+        Self::from_path_with_progress(path, name, refiner, |_| ())
+    }
 
-            Ok(new)
+    /// Like [`Data::from_path_with_stats`], but calls `progress` every time a piece of a file
+    /// has been counted. Files in a folder that aren't UTF-8 text are skipped.
+    pub fn from_path_with_progress<P, F>(
+        path: P,
+        name: &str,
+        refiner: &CorpusRefiner,
+        progress: F,
+    ) -> Result<(Self, CorpusStats), DataError>
+    where
+        P: AsRef<Path>,
+        F: Fn(CorpusProgress) + Sync,
+    {
+        let path = path.as_ref();
+        let is_dir = path.is_dir();
+
+        let files = if path.is_file() {
+            vec![path.to_path_buf()]
+        } else if is_dir {
+            std::fs::read_dir(path)?
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect()
         } else {
-            Err(DataError::FaultyPathError)
-        }
+            return Err(DataError::FaultyPathError);
+        };
+
+        let sizes = files
+            .iter()
+            .map(|f| Ok(f.metadata()?.len()))
+            .collect::<Result<Vec<_>, DataError>>()?;
+
+        let total_bytes = sizes.iter().sum();
+        let files_done = AtomicUsize::new(0);
+        let bytes_done = AtomicU64::new(0);
+
+        let report = |files: usize, bytes: u64| {
+            progress(CorpusProgress {
+                files,
+                total_files: sizes.len(),
+                bytes,
+                total_bytes,
+            })
+        };
+
+        let counted = files
+            .par_iter()
+            .zip(&sizes)
+            .map(|(path, &size)| {
+                let file_bytes = AtomicU64::new(0);
+                let on_chunk = |len: usize| {
+                    file_bytes.fetch_add(len as u64, Ordering::Relaxed);
+                    let bytes = bytes_done.fetch_add(len as u64, Ordering::Relaxed) + len as u64;
+                    report(files_done.load(Ordering::Relaxed), bytes);
+                };
+
+                let res = File::open(path)
+                    .map_err(DataError::from)
+                    .and_then(|f| Self::count_file(f, refiner, &on_chunk));
+
+                // Skipped files, or files that changed size, still count as fully read.
+                let rest = size.saturating_sub(file_bytes.into_inner());
+                let bytes = bytes_done.fetch_add(rest, Ordering::Relaxed) + rest;
+                let done = files_done.fetch_add(1, Ordering::Relaxed) + 1;
+                report(done, bytes);
+
+                match res {
+                    Err(DataError::UTF8Error(_)) if is_dir => Ok((
+                        Data::default(),
+                        CorpusStats {
+                            skipped_files: vec![path.display().to_string()],
+                            ..Default::default()
+                        },
+                    )),
+                    res => res,
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (res, stats) = counted.into_iter().fold(
+            Default::default(),
+            |(d1, s1): (Data, CorpusStats), (d2, s2)| (d1 + d2, s1 + s2),
+        );
+
+        let mut res = res.sorted();
+        res.name = name.into();
+        res.meta.refiner = refiner.config().cloned();
+
+        Ok((res, stats))
+    }
+
+    pub fn from_keystroke_log<P: AsRef<Path>>(
+        path: P,
+        name: &str,
+        options: &KeystrokeOptions,
+    ) -> Result<Self, DataError> {
+        let path = path.as_ref();
+        let log = std::fs::read_to_string(path).map_err(|source| DataError::ReadError {
+            path: path.display().to_string(),
+            source,
+        })?;
+
+        Self::from_keystrokes(&log, name, options)
     }
 
     pub fn from_file(file: File, name: &str, refiner: &CorpusRefiner) -> Result<Data, DataError> {
+        Self::from_file_with_stats(file, name, refiner).map(|(data, _)| data)
+    }
+
+    pub fn from_file_with_stats(
+        file: File,
+        name: &str,
+        refiner: &CorpusRefiner,
+    ) -> Result<(Data, CorpusStats), DataError> {
+        let (res, stats) = Self::count_file(file, refiner, &|_| ())?;
+
+        let mut res = res.sorted();
+        res.name = name.into();
+        res.meta.refiner = refiner.config().cloned();
+
+        Ok((res, stats))
+    }
+
+    fn count_file(
+        file: File,
+        refiner: &CorpusRefiner,
+        on_chunk: &(dyn Fn(usize) + Sync),
+    ) -> Result<(Data, CorpusStats), DataError> {
         let chunker = FileChunker::new(&file).map_err(|_| DataError::ChunkerInitError)?;
 
         let file_len = file.metadata()?.len() as usize;
@@ -230,19 +832,176 @@ impl Data {
 
         let chunks = chunker
             .chunks(chunk_count, Some(' '))
-            .map_err(|_| DataError::ChunkerChunkError)?;
+            .map_err(|_| DataError::ChunkerChunkError)?
+            .into_iter()
+            .map(std::str::from_utf8)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (res, mut stats) = Self::count_chunks(&chunks, refiner, on_chunk);
+        stats.files = 1;
 
-        let mut res = chunks
-            .into_par_iter()
-            .map(|chunk| std::str::from_utf8(chunk))
+        Ok((res, stats))
+    }
+
+    /// Counts consecutive pieces of a text in parallel. Each piece is refined from the state the
+    /// previous one ends in, and trigrams spanning two or more pieces are counted afterwards, so
+    /// the result is the same as counting the text as a whole.
+    ///
+    /// The state a piece starts in is guessed up front with [`CorpusRefiner::state_after`]. If a
+    /// guess turns out to be wrong, which can only happen when a multiple character rule matches
+    /// across the end of a piece, that piece is counted again.
+    pub fn from_chunks(chunks: &[&str], refiner: &CorpusRefiner) -> Self {
+        Self::from_chunks_with_stats(chunks, refiner).0
+    }
+
+    pub fn from_chunks_with_stats(chunks: &[&str], refiner: &CorpusRefiner) -> (Self, CorpusStats) {
+        Self::count_chunks(chunks, refiner, &|_| ())
+    }
+
+    /// Calls `on_chunk` with the length in bytes of every chunk that has been counted.
+    fn count_chunks(
+        chunks: &[&str],
+        refiner: &CorpusRefiner,
+        on_chunk: &(dyn Fn(usize) + Sync),
+    ) -> (Self, CorpusStats) {
+        struct Counted {
+            data: Data,
+            stats: CorpusStats,
+            head: Vec<char>,
+            tail: Vec<char>,
+            start: RefinerState,
+            end: RefinerState,
+        }
+
+        let count = |i: usize, start: RefinerState| {
+            let chunk = chunks[i];
+            let len = chunk.chars().count();
+
+            if start.skip >= len {
+                let end = RefinerState {
+                    skip: start.skip - len,
+                    previous: chunk.chars().last().unwrap_or(start.previous),
+                    ..start
+                };
+
+                return Counted {
+                    data: Data::default(),
+                    stats: CorpusStats {
+                        chars: len as u64,
+                        ..Default::default()
+                    },
+                    head: Vec::new(),
+                    tail: Vec::new(),
+                    start,
+                    end,
+                };
+            }
+
+            // rules may need to look at the start of the next chunks to find a match
+            let lookahead = chunks[i + 1..]
+                .iter()
+                .flat_map(|c| c.chars())
+                .take(refiner.longest_rule - 1);
+
+            let mut iter = chunk.chars().chain(lookahead).refine_from(refiner, start);
+            let mut head = Vec::with_capacity(2);
+            let mut tail = Vec::with_capacity(3);
+
+            let refined = std::iter::from_fn(|| match iter.consumed() < len {
+                true => iter.next(),
+                false => None,
+            })
             .flatten()
-            .map(|s| Data::from_iter(s.chars().refine(refiner).flatten()))
-            .reduce(|| Data::default(), |a, b| a + b)
-            .sorted();
+            .inspect(|&c| {
+                if head.len() < 2 {
+                    head.push(c);
+                }
+                tail.push(c);
+                if tail.len() > 2 {
+                    tail.remove(0);
+                }
+            });
+
+            let (data, discarded_trigrams) = Data::count_trigrams(refined);
+
+            let end = RefinerState {
+                skip: iter.consumed() - len,
+                ..iter.state()
+            };
+
+            let stats = CorpusStats {
+                chars: len as u64,
+                unmapped: iter.unmapped().clone(),
+                trigrams: data.total,
+                discarded_trigrams,
+                ..Default::default()
+            };
+
+            Counted {
+                data,
+                stats,
+                head,
+                tail,
+                start,
+                end,
+            }
+        };
 
-        res.name = name.into();
+        let mut counted = chunks
+            .par_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let counted = count(
+                    i,
+                    refiner.state_after(chunks[..i].iter().flat_map(|c| c.chars())),
+                );
+                on_chunk(chunk.len());
+                counted
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = RefinerState::default();
+
+        for (i, c) in counted.iter_mut().enumerate() {
+            if c.start != state {
+                *c = count(i, state);
+            }
+            state = c.end;
+        }
 
-        Ok(res)
+        let mut boundary = Data::default();
+        let mut boundary_discarded = 0;
+        let mut last = Vec::<char>::with_capacity(4);
+
+        for Counted { head, tail, .. } in counted.iter() {
+            let start = last.len();
+            last.extend(head);
+
+            for w in last.windows(3).take(start) {
+                if !boundary.add_trigram([w[0], w[1], w[2]]) {
+                    boundary_discarded += 1;
+                }
+            }
+
+            if tail.len() == 2 {
+                last.clone_from(tail);
+            } else {
+                last.drain(..last.len().saturating_sub(2));
+            }
+        }
+
+        let boundary_stats = CorpusStats {
+            trigrams: boundary.total,
+            discarded_trigrams: boundary_discarded,
+            ..Default::default()
+        };
+
+        counted
+            .into_iter()
+            .map(|c| (c.data, c.stats))
+            .fold((boundary, boundary_stats), |(d1, s1), (d2, s2)| {
+                (d1 + d2, s1 + s2)
+            })
     }
 
     pub fn save<P: AsRef<Path>>(self, folder: P) -> Result<(), DataError> {
@@ -282,31 +1041,14 @@ impl Data {
 #[cfg(test)]
 mod test {
     use super::*;
-    use time_this::time;
-
-    #[test]
-    fn load_test() {
-        // let folder = "/home/oxey/Repos/oxeylyzer/static/text/akl";
-        let folder =
-            "/home/oxey/Repos/oxeylyzer/static/text/english/iweb-corpus-samples-cleaned.txt";
-
-        let refiner = CorpusRefiner::new()
-            .include("abcdefghijklmnopqrstuvwxyz".chars(), true)
-            .include_qwerty_punct_casings()
-            .normalize_miscellaneous_punct()
-            .build();
-        // let refiner = CorpusRefiner::raw();
-
-        let data = time!(Data::from_path(folder, "shai", &refiner).unwrap());
-        time!(data.save("../data/").unwrap());
-    }
+    use crate::{REPEAT_KEY, SHIFT_CHAR};
 
     #[test]
     fn hundred_percent() {
         use assert_approx_eq::assert_approx_eq;
 
         let data = Data::load("../data/akl.json").unwrap();
-        let sum = data.trigrams.into_iter().map(|(_, f)| f).sum::<f32>();
+        let sum = data.frequencies().map(|(_, f)| f).sum::<f32>();
 
         assert_approx_eq!(sum, 100.0, 0.001f32)
     }
@@ -317,15 +1059,18 @@ mod test {
 
         let json = json!({
             "name": "test",
+            "total": 3,
             "trigrams": {
-                "abc": 0.0,
-                "def": 1.0,
+                "abc": 0,
+                "def": 3,
             }
         });
 
         let data = Data {
             name: "test".into(),
-            trigrams: FxIndexMap::from_iter([(['a', 'b', 'c'], 0.0), (['d', 'e', 'f'], 1.0)]),
+            meta: DataMeta::default(),
+            total: 3,
+            trigrams: FxIndexMap::from_iter([(['a', 'b', 'c'], 0), (['d', 'e', 'f'], 3)]),
         };
 
         assert_eq!(data, serde_json::from_value::<Data>(json.clone()).unwrap());
@@ -337,20 +1082,367 @@ mod test {
 
         let json = json!({
             "name": "test",
+            "total": 3,
             "trigrams": {
-                "abc": 0.0,
-                "def": 1.0,
+                "abc": 0,
+                "def": 3,
             }
         });
 
         let data = Data {
             name: "test".into(),
-            trigrams: FxIndexMap::from_iter([(['a', 'b', 'c'], 0.0), (['d', 'e', 'f'], 1.0)]),
+            meta: DataMeta::default(),
+            total: 3,
+            trigrams: FxIndexMap::from_iter([(['a', 'b', 'c'], 0), (['d', 'e', 'f'], 3)]),
         };
 
         assert_eq!(json, serde_json::to_value(&data).unwrap());
     }
 
+    #[test]
+    pub fn from_percentages() {
+        use assert_approx_eq::assert_approx_eq;
+        use serde_json::json;
+
+        let json = json!({
+            "name": "test",
+            "trigrams": {
+                "abc": 25.0,
+                "def": 75.0,
+            }
+        });
+
+        let data = serde_json::from_value::<Data>(json).unwrap();
+
+        assert_eq!(data.total(), LEGACY_TOTAL);
+        assert_eq!(data.count(&['a', 'b', 'c']), LEGACY_TOTAL / 4);
+        assert_approx_eq!(data.frequency(&['d', 'e', 'f']), 75.0);
+
+        let shai = Data::load("../data/shai.json").unwrap();
+        assert_eq!(shai.name(), "shai");
+        assert_approx_eq!(
+            shai.frequencies().map(|(_, f)| f as f64).sum::<f64>(),
+            100.0,
+            0.001
+        );
+    }
+
+    #[test]
+    fn legacy() {
+        use assert_approx_eq::assert_approx_eq;
+        use serde_json::json;
+
+        let json = json!({
+            "name": "test",
+            "trigrams": {
+                "abc": 1,
+                "def": 3,
+            }
+        });
+
+        let data = serde_json::from_value::<Data>(json).unwrap();
+
+        assert_eq!(data.total(), 4);
+        assert_eq!(data.count(&['d', 'e', 'f']), 3);
+
+        let json = json!({
+            "name": "test",
+            "trigrams": {
+                "abc": 0.25,
+                "def": 0.75,
+            }
+        });
+
+        let data = serde_json::from_value::<Data>(json).unwrap();
+
+        assert_eq!(data.total(), LEGACY_TOTAL);
+        assert_approx_eq!(data.frequency(&['a', 'b', 'c']), 25.0);
+    }
+
+    #[test]
+    fn merge_weighted() {
+        let short = Data::from("aaaa");
+        let long = Data::from("bbbbbbbb");
+
+        let merged = Data::from("aaaa") + Data::from("bbbbbbbb");
+        let reversed = long + short;
+
+        assert_eq!(merged.total(), 8);
+        assert_eq!(merged.count(&['a', 'a', 'a']), 2);
+        assert_eq!(merged.count(&['b', 'b', 'b']), 6);
+        assert_eq!(merged.frequency(&['b', 'b', 'b']), 75.0);
+        assert_eq!(merged.sorted().inner(), reversed.sorted().inner());
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        let text = "The BIG bookkeeper said: \"Hello, World!\" Ünïcødé 123 aaa\r\n\tOK.. Zz \
+            QUICK quiet thinking, abababc Straße";
+
+        let refiner = CorpusRefiner::builder()
+            .include("abcdefghijklmnopqrstuvwxyz".chars(), true)
+            .include_qwerty_punct_casings()
+            .include_ascii_whitespace()
+            .repeat_key(true)
+            .multiple_char_rule("qu", "q", true)
+            .multiple_char_rule("ing", "ŋ", true)
+            .multiple_char_rule("ab", "x", false)
+            .multiple_char_rule("babc", "y", false)
+            .multiple_char_rule("\r\n", "\n", false)
+            .multiple_char_rule("ß", "ss", false)
+            .build();
+
+        let expected = Data::from_iter(text.chars().refine(&refiner).flatten());
+        assert_eq!(expected.count(&[SHIFT_CHAR, 'b', 'i']), 1);
+        assert_eq!(expected.count(&['o', REPEAT_KEY, 'k']), 1);
+
+        for size in 1..=8 {
+            let bounds = text
+                .char_indices()
+                .map(|(i, _)| i)
+                .step_by(size)
+                .chain([text.len()])
+                .collect::<Vec<_>>();
+
+            let chunks = bounds
+                .windows(2)
+                .map(|w| &text[w[0]..w[1]])
+                .collect::<Vec<_>>();
+
+            assert_eq!(chunks.concat(), text);
+            assert_eq!(
+                Data::from_chunks(&chunks, &refiner),
+                expected,
+                "chunk size {size}"
+            );
+        }
+
+        let split = (0..text.len())
+            .filter(|&i| text.is_char_boundary(i))
+            .map(|i| Data::from_chunks(&[&text[..i], &text[i..]], &refiner))
+            .all(|data| data == expected);
+
+        assert!(split);
+    }
+
+    #[test]
+    fn refiner_provenance() {
+        let path = std::env::temp_dir().join("oxeygen-provenance.txt");
+        std::fs::write(&path, "Some text to count.").unwrap();
+
+        let config = RefinerConfig {
+            include: "abcdefghijklmnopqrstuvwxyz".into(),
+            space: true,
+            ..Default::default()
+        };
+
+        let data = Data::from_path(&path, "provenance", &config.build()).unwrap();
+        assert_eq!(data.meta().refiner.as_ref(), Some(&config));
+        assert_eq!(data.count(&['s', 'o', 'm']), 0);
+        assert_eq!(data.count(&['o', 'm', 'e']), 1);
+
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(serde_json::from_value::<Data>(json).unwrap(), data);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn progress() {
+        let folder = std::env::temp_dir().join("oxeygen-progress");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("a.txt"), "the cat").unwrap();
+        std::fs::write(folder.join("b.txt"), "the hat").unwrap();
+        std::fs::write(folder.join("c.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let refiner = CorpusRefiner::builder()
+            .include("abcdefghijklmnopqrstuvwxyz".chars(), false)
+            .build();
+
+        let reports = std::sync::Mutex::new(Vec::new());
+        let (data, stats) = Data::from_path_with_progress(&folder, "progress", &refiner, |p| {
+            reports.lock().unwrap().push(p)
+        })
+        .unwrap();
+
+        let reports = reports.into_inner().unwrap();
+        let last = reports.iter().max_by_key(|p| (p.files, p.bytes)).unwrap();
+
+        assert_eq!(last.files, 3);
+        assert_eq!(last.total_files, 3);
+        assert_eq!(last.bytes, 17);
+        assert_eq!(last.total_bytes, 17);
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.skipped_files.len(), 1);
+        assert!(stats.skipped_files[0].ends_with("c.bin"));
+        assert_eq!(data.count(&['t', 'h', 'e']), 2);
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn load_errors() {
+        let path = std::env::temp_dir().join("oxeygen-bad-corpus.json");
+        std::fs::write(&path, "{\n  \"name\": \"bad\",\n  \"total\": x\n}").unwrap();
+
+        let err = Data::load(&path).unwrap_err();
+        assert!(matches!(&err, DataError::ParseError { source, .. } if source.line() == 3));
+        assert!(err.to_string().contains("oxeygen-bad-corpus.json"));
+
+        assert!(matches!(
+            Data::load("missing.json"),
+            Err(DataError::ReadError { path, .. }) if path == "missing.json"
+        ));
+    }
+
+    #[test]
+    fn keystrokes() {
+        let log = "shift+t\nh\ne\nbackspace\ne\nspace";
+        let data = Data::from_keystrokes(log, "log", &KeystrokeOptions::default()).unwrap();
+
+        assert_eq!(data.name(), "log");
+        assert_eq!(data.total(), 5);
+        assert_eq!(data.count(&['h', 'e', crate::BACKSPACE_CHAR]), 1);
+        assert_eq!(data.count(&[crate::BACKSPACE_CHAR, 'e', ' ']), 1);
+
+        assert!(matches!(
+            Data::from_keystrokes("a\nb\nfoo+c", "log", &KeystrokeOptions::default()),
+            Err(DataError::KeystrokeError(3, _))
+        ));
+        assert!(matches!(
+            Data::from_keystroke_log("missing.log", "log", &KeystrokeOptions::default()),
+            Err(DataError::ReadError { path, .. }) if path == "missing.log"
+        ));
+    }
+
+    #[test]
+    fn stats() {
+        let refiner = CorpusRefiner::builder()
+            .include("abcdefghijklmnopqrstuvwxyz".chars(), true)
+            .include_space()
+            .build();
+
+        let text = "The year 2024, or 2O24.";
+        let chunks = [&text[..10], &text[10..]];
+        let (data, stats) = Data::from_chunks_with_stats(&chunks, &refiner);
+
+        assert_eq!(stats.chars, 23);
+        assert_eq!(stats.unmapped_chars(), 9);
+        assert_eq!(stats.top_unmapped(2), [('2', 4), ('4', 2)]);
+        assert_eq!(stats.trigrams, data.total());
+        assert_eq!(stats.trigrams + stats.discarded_trigrams, 23);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(json.contains(r#""unmapped":{"2":4,"4":2,"#));
+
+        assert_eq!(data.top_ngrams::<1>(2), [([' '], 2), (['e'], 2)]);
+        assert_eq!(data.top_ngrams::<2>(1), [([' ', 'o'], 1)]);
+        assert_eq!(data.top_ngrams::<3>(100).len(), data.inner().len());
+    }
+
+    #[test]
+    fn blend() {
+        use assert_approx_eq::assert_approx_eq;
+
+        let mut short = Data::from("aaaa");
+        short.name = "short".into();
+        let mut long = Data::from("bbbbbbbbbbbbbbbbbbbbbb");
+        long.name = "long".into();
+
+        let blended = Data::blend(&[(&short, 60.0), (&long, 40.0)], "mix").unwrap();
+
+        assert_eq!(blended.name(), "mix");
+        assert_eq!(blended.total(), 22);
+        assert_approx_eq!(blended.frequency(&['a', 'a', 'a']), 60.0, 3.0);
+        assert_approx_eq!(blended.frequency(&['b', 'b', 'b']), 40.0, 3.0);
+
+        let recipe = blended.meta().blend.as_ref().unwrap();
+        assert_eq!(recipe[0].name, "short");
+        assert_approx_eq!(recipe[1].weight, 0.4);
+
+        let json = serde_json::to_value(&blended).unwrap();
+        assert_eq!(blended, serde_json::from_value::<Data>(json).unwrap());
+
+        let empty = Data::default();
+        let with_empty =
+            Data::blend(&[(&short, 60.0), (&long, 40.0), (&empty, 50.0)], "mix").unwrap();
+        assert_eq!(with_empty.inner(), blended.inner());
+        assert_eq!(with_empty.meta().blend.as_ref().unwrap().len(), 2);
+
+        assert!(matches!(
+            Data::blend(&[], "mix"),
+            Err(DataError::EmptyBlendError)
+        ));
+        assert!(matches!(
+            Data::blend(&[(&short, 1.0), (&long, -1.0)], "mix"),
+            Err(DataError::BlendWeightError(n, _)) if n == "long"
+        ));
+    }
+
+    #[test]
+    fn prune() {
+        let data = || Data::from("aaaaabababc");
+
+        let mut pruned = data();
+        let report = pruned.prune_below(25.0);
+        assert_eq!(
+            pruned.inner().keys().collect::<Vec<_>>(),
+            [&['a', 'a', 'a']]
+        );
+        assert_eq!(report.trigrams_before, 5);
+        assert_eq!(report.total_before, 9);
+        assert_eq!(report.discarded, 6);
+        assert_eq!(pruned.frequency(&['a', 'a', 'a']), 100.0);
+
+        let mut pruned = data();
+        let report = pruned.keep_top(2);
+        assert_eq!(report.trigrams_after, 2);
+        assert_eq!(pruned.total(), 5);
+        assert!((report.discarded_fraction() - 4.0 / 9.0).abs() < 1e-9);
+
+        let mut pruned = data();
+        pruned.keep_mass(50.0);
+        assert_eq!(pruned.total(), 5);
+        assert_eq!(data().keep_mass(100.0).discarded, 0);
+
+        let mut folded = data();
+        let report = folded.fold_rare_chars(1);
+        assert_eq!(report.folded, ['b', 'c']);
+        assert_eq!(report.discarded, 0);
+        assert_eq!(report.trigrams_after, 5);
+        assert_eq!(folded.total(), 9);
+        assert_eq!(folded.count(&['a', OTHER_CHAR, 'a']), 2);
+        assert_eq!(folded.count(&[OTHER_CHAR, 'a', OTHER_CHAR]), 2);
+        assert_eq!(folded.count(&['a', OTHER_CHAR, OTHER_CHAR]), 1);
+    }
+
+    #[test]
+    fn refine() {
+        let refiner = CorpusRefiner::builder()
+            .include("abcdefghijklmnopqrstuvwxyz".chars(), true)
+            .include_space()
+            .dead_key([('é', 'e')].into_iter(), '´')
+            .multiple_char_rule("qu", "%", true)
+            .repeat_key(true)
+            .build();
+
+        for text in ["Hello World", "quiet quit", "Café au LAIT", "Quoting QUIET"] {
+            let direct = Data::from(text.chars().refine(&refiner)).sorted();
+            let refined = Data::from(text).refine(&refiner);
+
+            assert_eq!(refined.inner(), direct.inner(), "{text}");
+        }
+
+        let config = crate::presets::refiner_preset("english").unwrap();
+        let refined = Data::from("“Hi” 2 you").refine(&config.build());
+
+        assert_eq!(refined.meta().refiner.as_ref(), Some(&config));
+        assert_eq!(refined.count(&[SHIFT_CHAR, '\'', 'h']), 1);
+        assert_eq!(refined.count(&['i', SHIFT_CHAR, '\'']), 1);
+        assert_eq!(refined.count(&[' ', 'y', 'o']), 1);
+        assert!(refined.inner().keys().all(|t| !t.contains(&'2')));
+    }
+
     #[test]
     pub fn err() {
         use serde_json::json;
@@ -375,9 +1467,9 @@ mod test {
         assert!(serde_json::from_value::<Data>(json2).is_err());
     }
 
-    fn trigram(data: &Data, t: &str) -> f32 {
+    fn trigram(data: &Data, t: &str) -> u64 {
         let t: [char; 3] = t.chars().collect::<Vec<_>>().try_into().unwrap();
-        *data.trigrams.get(&t).unwrap_or(&0.0)
+        data.count(&t)
     }
 
     #[test]
@@ -385,17 +1477,11 @@ mod test {
         let s = "the will of the people.";
 
         let data = Data::from(s);
-        let min_val = data
-            .trigrams
-            .values()
-            .min_by(|a, b| a.partial_cmp(b).expect("No NaN is allowed here"))
-            .unwrap_or(&1.0);
-
-        dbg!(min_val);
-
-        assert_eq!(trigram(&data, "the"), 2.0 * min_val);
-        assert_eq!(trigram(&data, "peo"), 1.0 * min_val);
-        assert_eq!(trigram(&data, "e. "), 0.0 * min_val);
-        assert_eq!(trigram(&data, "dof"), 0.0 * min_val);
+
+        assert_eq!(data.total(), s.chars().count() as u64 - 2);
+        assert_eq!(trigram(&data, "the"), 2);
+        assert_eq!(trigram(&data, "peo"), 1);
+        assert_eq!(trigram(&data, "e. "), 0);
+        assert_eq!(trigram(&data, "dof"), 0);
     }
 }
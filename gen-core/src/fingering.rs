@@ -0,0 +1,206 @@
+use itertools::Itertools;
+use libdof::dofinitions::Finger;
+
+use crate::{
+    keyboard::{Keyboard, Pos},
+    layout::Layout,
+    optimizer::Optimizer,
+    trigram_types::TrigramTypes,
+};
+
+/// The most fingering variants [`Optimizer::optimize_fingerings`] searches over, since every one
+/// of them needs its own optimizer and generated layouts.
+pub const MAX_VARIANTS: usize = 4096;
+
+/// The fingers that are allowed to press each position of a keyboard. Every combination of these
+/// is a fingering variant that can be searched over with [`Optimizer::optimize_fingerings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingeringOptions(Box<[Box<[Finger]>]>);
+
+impl FingeringOptions {
+    /// Returns `None` if any position doesn't allow a single finger.
+    pub fn new(options: Vec<Vec<Finger>>) -> Option<Self> {
+        if options.iter().any(|o| o.is_empty()) {
+            return None;
+        }
+
+        let options = options
+            .into_iter()
+            .map(|o| o.into_iter().unique().collect::<Box<[_]>>())
+            .collect();
+
+        Some(Self(options))
+    }
+
+    /// Only allows the current fingering of the keyboard.
+    pub fn fixed(keyboard: &Keyboard) -> Self {
+        let options = keyboard
+            .fingering()
+            .iter()
+            .map(|&f| Box::from([f]))
+            .collect();
+
+        Self(options)
+    }
+
+    /// Allows an additional finger on a position. Returns `None` if the position doesn't exist.
+    pub fn allow(&mut self, pos: Pos, finger: Finger) -> Option<()> {
+        let options = self.0.get_mut(pos)?;
+
+        if !options.contains(&finger) {
+            *options = options.iter().copied().chain([finger]).collect();
+        }
+
+        Some(())
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<&[Finger]> {
+        self.0.get(pos).map(|o| &**o)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The amount of fingering variants. This grows exponentially with the amount of positions that
+    /// allow more than one finger, so it saturates at `usize::MAX`.
+    pub fn variant_count(&self) -> usize {
+        self.0.iter().fold(1, |n, o| n.saturating_mul(o.len()))
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = Vec<Finger>> + '_ {
+        self.0
+            .iter()
+            .map(|o| o.iter().copied())
+            .multi_cartesian_product()
+    }
+}
+
+/// The best layout that was found for a single fingering variant.
+#[derive(Debug, Clone)]
+pub struct FingeringResult {
+    pub fingering: Box<[Finger]>,
+    pub layout: Layout,
+    pub score: f32,
+}
+
+impl<'a> Optimizer<'a> {
+    /// Searches over every fingering variant in `options` as well as key placement. For each variant
+    /// `runs` layouts are generated from `template`, and the best one is kept. The results are sorted
+    /// from best to worst, so the first one holds the fingering that wins overall.
+    ///
+    /// Returns `None` if `trigram_types` aren't for the keyboard of the optimizer, if `options` or
+    /// `template` don't have the same amount of keys, or if there are more than [`MAX_VARIANTS`]
+    /// variants.
+    pub fn optimize_fingerings(
+        &self,
+        trigram_types: &TrigramTypes,
+        options: &FingeringOptions,
+        template: &Layout,
+        runs: usize,
+    ) -> Option<Vec<FingeringResult>> {
+        if trigram_types.keyboard() != self.keyboard()
+            || options.len() != self.len()
+            || template.len() != self.len()
+            || options.variant_count() > MAX_VARIANTS
+        {
+            return None;
+        }
+
+        let mut results = Vec::with_capacity(options.variant_count());
+
+        for fingering in options.variants() {
+            let keyboard = trigram_types.keyboard().with_fingering(&fingering)?;
+            let types = trigram_types.with_keyboard(keyboard);
+            let optimizer = self.with_keyboard(&types, self.weights())?;
+
+            let mut template = template.clone();
+            template.set_fingering(&fingering)?;
+
+            let best = (0..runs.max(1))
+                .map(|_| {
+                    let layout = optimizer.generate_from(&template);
+                    let score = optimizer.calc_score(&layout);
+                    (layout, score)
+                })
+                .max_by(|(_, s1), (_, s2)| s1.total_cmp(s2));
+
+            if let Some((layout, score)) = best {
+                results.push(FingeringResult {
+                    fingering: fingering.into(),
+                    layout,
+                    score,
+                });
+            }
+        }
+
+        results.sort_by(|r1, r2| r2.score.total_cmp(&r1.score));
+
+        Some(results)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::Data, weights::Weights};
+    use libdof::dofinitions::Finger::*;
+
+    #[test]
+    fn angle_mod_variants() {
+        #[rustfmt::skip]
+        let fingering = [
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+        ];
+
+        let keyboard = Keyboard::new(&fingering);
+        let types = TrigramTypes::with_defaults(keyboard.clone());
+
+        let data = Data::load("../data/shai.json").expect("couldn't load read data");
+        let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
+
+        let optimizer = Optimizer::new(&types, &data, weights);
+
+        let mut options = FingeringOptions::fixed(&keyboard);
+        options.allow(20, LR).unwrap();
+        options.allow(21, LM).unwrap();
+        options.allow(22, LI).unwrap();
+
+        assert_eq!(options.variant_count(), 8);
+        assert!(options.allow(30, LP).is_none());
+
+        let chars = "abcdefghijklmnopqrstuvwxyz,.';".chars().collect::<Vec<_>>();
+        let template = optimizer.layout(&chars, &fingering).unwrap();
+
+        let results = optimizer
+            .optimize_fingerings(&types, &options, &template, 2)
+            .unwrap();
+
+        assert_eq!(results.len(), 8);
+        assert!(results.windows(2).all(|r| r[0].score >= r[1].score));
+
+        for result in results.iter() {
+            assert_eq!(result.layout.fingers(), &*result.fingering);
+            assert_eq!(&result.fingering[..20], &fingering[..20]);
+        }
+
+        let any = FingeringOptions::new(vec![vec![LP, LR, LM, LI, RI, RM, RR, RP]; 30]).unwrap();
+        assert_eq!(any.variant_count(), usize::MAX);
+        assert!(optimizer
+            .optimize_fingerings(&types, &any, &template, 1)
+            .is_none());
+
+        let other = TrigramTypes::with_defaults(Keyboard::from_shape(&[12, 12, 6]));
+        assert!(optimizer
+            .optimize_fingerings(&other, &options, &template, 1)
+            .is_none());
+    }
+}
@@ -1,17 +1,314 @@
-use libdof::dofinitions::Finger;
+use libdof::{dofinitions::Finger, Dof};
+use thiserror::Error;
 
 pub type Pos = usize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+/// The hand a finger belongs to, and where it sits on that hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerInfo {
+    pub hand: Hand,
+    /// Position of the finger on its hand counting from the outside in, so moving to a higher
+    /// order is moving inwards. Fingers without an order, like the thumbs in the default model,
+    /// never take part in rolls, onehands or redirects.
+    pub order: Option<u8>,
+}
+
+impl FingerInfo {
+    pub fn is_inward(&self, next: &Self) -> bool {
+        self.hand == next.hand
+            && matches!((self.order, next.order), (Some(o1), Some(o2)) if o1 < o2)
+    }
+
+    pub fn is_outward(&self, next: &Self) -> bool {
+        self.hand == next.hand
+            && matches!((self.order, next.order), (Some(o1), Some(o2)) if o1 > o2)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum HandModelError {
+    #[error("Finger '{0}' is assigned to a hand more than once")]
+    DuplicateFinger(Finger),
+
+    #[error("Fingers '{0}' and '{1}' have the same order on the same hand")]
+    DuplicateOrder(Finger, Finger),
+
+    #[error("Finger '{0}' is used by the keyboard but isn't assigned to a hand")]
+    UnassignedFinger(Finger),
+}
+
+/// Describes which hand every finger belongs to and in what order the fingers of a hand are
+/// placed. The default model is a regular two-handed one, where pinky to index go from the outside
+/// in and thumbs are on their own hand without an order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandModel([Option<FingerInfo>; 10]);
+
+impl Default for HandModel {
+    fn default() -> Self {
+        use Finger::*;
+
+        Self::from_order(&[LP, LR, LM, LI], &[RP, RR, RM, RI])
+            .and_then(|m| m.assign(LT, Hand::Left, None))
+            .and_then(|m| m.assign(RT, Hand::Right, None))
+            .expect("the default hand model is valid")
+    }
+}
+
+impl HandModel {
+    /// A model without any fingers assigned to a hand.
+    pub fn empty() -> Self {
+        Self([None; 10])
+    }
+
+    /// Creates a model from the fingers of both hands, each ordered from the outside in. Thumbs or
+    /// other fingers without an order can be added with [`HandModel::assign`].
+    pub fn from_order(left: &[Finger], right: &[Finger]) -> Result<Self, HandModelError> {
+        let left = left.iter().enumerate().map(|(i, &f)| (f, Hand::Left, i));
+        let right = right.iter().enumerate().map(|(i, &f)| (f, Hand::Right, i));
+
+        left.chain(right)
+            .try_fold(Self::empty(), |model, (f, hand, i)| {
+                model.assign(f, hand, Some(i as u8))
+            })
+    }
+
+    pub fn assign(
+        mut self,
+        finger: Finger,
+        hand: Hand,
+        order: Option<u8>,
+    ) -> Result<Self, HandModelError> {
+        if self.0[finger as usize].is_some() {
+            return Err(HandModelError::DuplicateFinger(finger));
+        }
+
+        if let Some(order) = order {
+            let duplicate = self
+                .assigned()
+                .find(|(_, info)| info.hand == hand && info.order == Some(order));
+
+            if let Some((other, _)) = duplicate {
+                return Err(HandModelError::DuplicateOrder(other, finger));
+            }
+        }
+
+        self.0[finger as usize] = Some(FingerInfo { hand, order });
+        Ok(self)
+    }
+
+    pub fn info(&self, finger: Finger) -> Option<FingerInfo> {
+        self.0[finger as usize]
+    }
+
+    pub fn hand(&self, finger: Finger) -> Option<Hand> {
+        self.info(finger).map(|i| i.hand)
+    }
+
+    pub fn assigned(&self) -> impl Iterator<Item = (Finger, FingerInfo)> + '_ {
+        use Finger::*;
+
+        [LP, LR, LM, LI, LT, RT, RI, RM, RR, RP]
+            .into_iter()
+            .filter_map(|f| self.info(f).map(|i| (f, i)))
+    }
+
+    /// Checks that every finger in `fingers` is assigned to a hand.
+    pub fn validate(&self, fingers: &[Finger]) -> Result<(), HandModelError> {
+        match fingers.iter().find(|&&f| self.info(f).is_none()) {
+            Some(&f) => Err(HandModelError::UnassignedFinger(f)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Physical placement of a key in key units, where `x` and `y` are the center of the key after
+/// rotating it by `angle` degrees clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PhysicalKey {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub angle: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Keyboard {
     fingers: Box<[Finger]>,
+    hands: HandModel,
+    shape: Box<[usize]>,
+    physical: Option<Box<[PhysicalKey]>>,
 }
 
 impl Keyboard {
+    /// Creates a keyboard with the default hand model. Rows are guessed from the fingering: a new
+    /// row starts whenever a finger is further left than the one before it. Use
+    /// [`Keyboard::with_shape`] to set them explicitly.
     pub fn new(fingers: &[Finger]) -> Self {
+        Self::with_hands(fingers, HandModel::default())
+            .expect("the default hand model contains every finger")
+    }
+
+    /// Creates a keyboard that classifies hands and finger order with a custom model. Fails if
+    /// any finger of the keyboard isn't part of the model. Rows are guessed like with
+    /// [`Keyboard::new`], going by where the model places each finger.
+    pub fn with_hands(fingers: &[Finger], hands: HandModel) -> Result<Self, HandModelError> {
+        hands.validate(fingers)?;
+
+        let shape = Self::guess_shape(fingers, &hands);
         let fingers = fingers.into();
 
-        Self { fingers }
+        Ok(Self {
+            fingers,
+            hands,
+            shape,
+            physical: None,
+        })
+    }
+
+    /// Creates a keyboard with a traditional fingering for rows of the given lengths. Each row is
+    /// split in half, and keys are assigned by their column counted from the middle: the two
+    /// innermost columns go to the index finger, followed by middle, ring, and pinky for all
+    /// remaining columns. Rows after the first with fewer than four keys are thumb keys.
+    pub fn from_shape(shape: &[usize]) -> Self {
+        use Finger::*;
+
+        let finger = |column: usize, fingers: [Finger; 4]| fingers[column.saturating_sub(1).min(3)];
+
+        let fingers = shape
+            .iter()
+            .enumerate()
+            .flat_map(|(row, &len)| {
+                let left = len / 2;
+
+                (0..len).map(move |i| match (row > 0 && len < 4, i < left) {
+                    (true, true) => LT,
+                    (true, false) => RT,
+                    (false, true) => finger(left - i - 1, [LI, LM, LR, LP]),
+                    (false, false) => finger(i - left, [RI, RM, RR, RP]),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Self::new(&fingers)
+            .with_shape(shape)
+            .expect("the shape adds up to the amount of fingers")
+    }
+
+    /// Creates a keyboard with the fingering and shape of the main layer of a .dof layout.
+    pub fn from_dof(dof: &Dof) -> Self {
+        let fingering = dof.fingering();
+        let shape = fingering.rows().map(Vec::len).collect::<Vec<_>>();
+        let fingers = fingering.rows().flatten().copied().collect::<Vec<_>>();
+
+        Self::new(&fingers)
+            .with_shape(&shape)
+            .expect("the shape adds up to the amount of fingers")
+    }
+
+    /// Splits the keys into rows, starting a new one whenever a finger is further left than the one
+    /// before it. Going from left to right, the left hand goes from the outside in and the right
+    /// hand from the inside out. Fingers without an order are placed on the inside of their hand.
+    fn guess_shape(fingers: &[Finger], hands: &HandModel) -> Box<[usize]> {
+        let column = |f: Finger| match hands.info(f) {
+            Some(FingerInfo {
+                hand: Hand::Left,
+                order,
+            }) => (0, order.map_or(i16::MAX, i16::from)),
+            Some(FingerInfo {
+                hand: Hand::Right,
+                order,
+            }) => (1, order.map_or(i16::MIN, |o| -i16::from(o))),
+            None => (0, 0),
+        };
+
+        let mut shape = Vec::new();
+        let mut row_len = 0;
+
+        for (i, &f) in fingers.iter().enumerate() {
+            if i > 0 && column(f) < column(fingers[i - 1]) {
+                shape.push(row_len);
+                row_len = 0;
+            }
+            row_len += 1;
+        }
+
+        if row_len > 0 {
+            shape.push(row_len);
+        }
+
+        shape.into()
+    }
+
+    /// Sets the amount of keys on each row. Returns `None` if these don't add up to the amount of
+    /// keys on the keyboard.
+    pub fn with_shape(mut self, shape: &[usize]) -> Option<Self> {
+        if shape.iter().sum::<usize>() == self.len() {
+            self.shape = shape.into();
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Sets the physical placement of every key. Returns `None` if the amount of keys doesn't
+    /// match.
+    pub fn with_physical(mut self, keys: Vec<PhysicalKey>) -> Option<Self> {
+        if keys.len() == self.len() {
+            self.physical = Some(keys.into());
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Creates a keyboard with the same shape, physical keys and hand model but a different
+    /// fingering. Returns `None` if the amount of fingers doesn't match, or if a finger isn't part
+    /// of the hand model.
+    pub fn with_fingering(&self, fingers: &[Finger]) -> Option<Self> {
+        if fingers.len() == self.len() {
+            let mut keyboard = Self::with_hands(fingers, self.hands.clone())
+                .ok()?
+                .with_shape(&self.shape)?;
+            keyboard.physical = self.physical.clone();
+
+            Some(keyboard)
+        } else {
+            None
+        }
+    }
+
+    /// The amount of keys on each row.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// The physical placement of every key, if known.
+    pub fn physical(&self) -> Option<&[PhysicalKey]> {
+        self.physical.as_deref()
+    }
+
+    /// The positions on each row.
+    pub fn rows(&self) -> impl Iterator<Item = std::ops::Range<Pos>> + '_ {
+        self.shape.iter().scan(0, |start, &len| {
+            let row = *start..*start + len;
+            *start += len;
+            Some(row)
+        })
+    }
+
+    pub fn hands(&self) -> &HandModel {
+        &self.hands
+    }
+
+    pub fn get_finger_info(&self, pos: Pos) -> Option<FingerInfo> {
+        self.get_finger(pos).and_then(|f| self.hands.info(f))
     }
 
     pub fn fingering(&self) -> &[Finger] {
@@ -0,0 +1,241 @@
+//! Reading keystroke logs, so layouts can be tuned on how people actually type instead of on
+//! finished text.
+//!
+//! A log has one key event per line, optionally preceded by a timestamp that is ignored. An
+//! event is a key with any number of modifiers in front of it, separated by `+`:
+//!
+//! ```text
+//! # comments start with a hash
+//! 1700000000.120 shift+h
+//! 1700000000.250 e
+//! backspace
+//! ctrl+c
+//! space
+//! ```
+//!
+//! Keys are either a single character or one of the names `space`, `enter`/`return`, `tab`,
+//! `backspace`, `plus` and `hash`. Other named keys like `esc`, `left` or `f5` aren't part of a
+//! layout, and break up trigrams like filtered out characters do. Names that aren't known at all
+//! are an error, so typos don't go unnoticed. Uppercase letters are read as shift and the lowercase
+//! letter.
+
+use crate::{BACKSPACE_CHAR, REPLACEMENT_CHAR, SHIFT_CHAR};
+
+const SHORTCUT_MODIFIERS: &[&str] = &[
+    "ctrl", "control", "alt", "option", "opt", "meta", "cmd", "command", "super", "win", "altgr",
+];
+
+/// Named keys that aren't part of a layout, besides modifiers and function keys.
+const OTHER_KEYS: &[&str] = &[
+    "esc",
+    "escape",
+    "left",
+    "right",
+    "up",
+    "down",
+    "home",
+    "end",
+    "pageup",
+    "pagedown",
+    "pgup",
+    "pgdn",
+    "insert",
+    "ins",
+    "delete",
+    "del",
+    "capslock",
+    "caps",
+    "numlock",
+    "scrolllock",
+    "printscreen",
+    "pause",
+    "menu",
+];
+
+fn is_other_key(name: &str) -> bool {
+    let function_key = name
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| (1..=24).contains(&n));
+
+    function_key
+        || name == "shift"
+        || SHORTCUT_MODIFIERS.contains(&name)
+        || OTHER_KEYS.contains(&name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeystrokeOptions {
+    /// Character that backspace presses are counted as.
+    pub backspace: char,
+    /// Count the keys of shortcuts like `ctrl+c`. Modifiers other than shift aren't part of a
+    /// layout, so only the key itself is counted. When disabled, shortcuts break up trigrams.
+    pub shortcuts: bool,
+}
+
+impl Default for KeystrokeOptions {
+    fn default() -> Self {
+        Self {
+            backspace: BACKSPACE_CHAR,
+            shortcuts: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyEvent {
+    Key {
+        key: char,
+        shift: bool,
+        shortcut: bool,
+    },
+    /// A key that isn't part of a layout.
+    Other,
+}
+
+fn parse_event(event: &str, options: &KeystrokeOptions) -> Result<KeyEvent, String> {
+    // a trailing `++` means the key itself is a plus
+    let (modifiers, key) = match event.strip_suffix("++") {
+        _ if event == "+" => ("", event),
+        Some(modifiers) => (modifiers, "+"),
+        None => event.rsplit_once('+').unwrap_or(("", event)),
+    };
+
+    if key.is_empty() {
+        return Err(format!("'{event}' is missing a key"));
+    }
+
+    let mut shift = false;
+    let mut shortcut = false;
+
+    for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+        let modifier = modifier.to_lowercase();
+
+        if modifier == "shift" {
+            shift = true;
+        } else if SHORTCUT_MODIFIERS.contains(&modifier.as_str()) {
+            shortcut = true;
+        } else {
+            return Err(format!("unknown modifier '{modifier}'"));
+        }
+    }
+
+    let mut chars = key.chars();
+    let key = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_uppercase() && c.to_lowercase().count() == 1 => {
+            shift = true;
+            c.to_lowercase().next().unwrap_or(c)
+        }
+        (Some(c), None) => c,
+        _ => match key.to_lowercase().as_str() {
+            "space" => ' ',
+            "enter" | "return" => '\n',
+            "tab" => '\t',
+            "backspace" => options.backspace,
+            "plus" => '+',
+            "hash" => '#',
+            name if is_other_key(name) => return Ok(KeyEvent::Other),
+            _ => return Err(format!("unknown key '{key}'")),
+        },
+    };
+
+    Ok(KeyEvent::Key {
+        key,
+        shift,
+        shortcut,
+    })
+}
+
+/// Turns a keystroke log into the sequence of keys that were pressed, including shift. Like with
+/// corpus refinement, consecutive shifted keys share a single shift press. Errors contain the line
+/// number and what was wrong with it.
+pub fn key_presses(log: &str, options: &KeystrokeOptions) -> Result<Vec<char>, (usize, String)> {
+    let mut presses = Vec::with_capacity(log.len());
+    let mut shift_pressed = false;
+
+    for (i, line) in log.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let event = match line.split_once(char::is_whitespace) {
+            Some((time, event)) if time.parse::<f64>().is_ok() => event.trim(),
+            _ => line,
+        };
+
+        match parse_event(event, options).map_err(|e| (i + 1, e))? {
+            KeyEvent::Key { shortcut: true, .. } if !options.shortcuts => {
+                presses.push(REPLACEMENT_CHAR);
+                shift_pressed = false;
+            }
+            KeyEvent::Key { key, shift, .. } => {
+                if shift && !shift_pressed {
+                    presses.push(SHIFT_CHAR);
+                }
+                shift_pressed = shift;
+                presses.push(key);
+            }
+            KeyEvent::Other => {
+                presses.push(REPLACEMENT_CHAR);
+                shift_pressed = false;
+            }
+        }
+    }
+
+    Ok(presses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presses() {
+        let log = "\
+            # greeting
+            1700000000.1 shift+h
+            1700000000.2 I
+            e
+            backspace
+            space
+            ctrl+c
+            shift++
+            +
+            left
+            F5
+            t";
+
+        let presses = key_presses(log, &KeystrokeOptions::default())
+            .unwrap()
+            .into_iter()
+            .collect::<String>();
+
+        assert_eq!(presses, "⇑hie⌫ �⇑++��t");
+
+        let options = KeystrokeOptions {
+            backspace: '<',
+            shortcuts: true,
+        };
+        let presses = key_presses(log, &options)
+            .unwrap()
+            .into_iter()
+            .collect::<String>();
+
+        assert_eq!(presses, "⇑hie< c⇑++��t");
+
+        assert_eq!(
+            key_presses("a\nhyper+b", &options),
+            Err((2, "unknown modifier 'hyper'".into()))
+        );
+        assert_eq!(
+            key_presses("a\nb\nbakspace", &options),
+            Err((3, "unknown key 'bakspace'".into()))
+        );
+        assert_eq!(
+            key_presses("shift+", &options),
+            Err((1, "'shift+' is missing a key".into()))
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::str::FromStr;
+
+use libdof::dofinitions::Finger;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::keyboard::{Keyboard, PhysicalKey};
+
+/// How fingers are assigned to the keys of a keyboard-layout-editor board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FingerAssignment {
+    /// Use a finger annotation like `LI` in the legends of a key when there is one, and the
+    /// column-based default otherwise.
+    #[default]
+    Auto,
+    /// Every key must have a finger annotation in its legends.
+    Legends,
+    /// Ignore the legends and assign every key based on its column.
+    Columns,
+}
+
+#[derive(Debug, Error)]
+pub enum KleError {
+    #[error("{0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Expected a list of rows")]
+    NotAList,
+
+    #[error("Row {0} should be a list of keys and properties")]
+    InvalidRow(usize),
+
+    #[error("Row {0} contains something that is neither a key nor a set of properties")]
+    InvalidItem(usize),
+
+    #[error("Rotation can only be set on the first key of a row, but row {0} sets it later")]
+    MisplacedRotation(usize),
+
+    #[error("The board doesn't contain any keys")]
+    NoKeys,
+
+    #[error("Key {index} on row {row} ('{legend}') doesn't have a finger annotation")]
+    MissingFinger {
+        row: usize,
+        index: usize,
+        legend: String,
+    },
+}
+
+struct KleKey {
+    physical: PhysicalKey,
+    legend: String,
+    row: usize,
+    index: usize,
+}
+
+impl KleKey {
+    fn annotated_finger(&self) -> Option<Finger> {
+        self.legend
+            .split('\n')
+            .map(|l| l.trim().to_uppercase())
+            .find(|l| {
+                matches!(
+                    l.as_str(),
+                    "LP" | "LR" | "LM" | "LI" | "LT" | "RT" | "RI" | "RM" | "RR" | "RP"
+                )
+            })
+            .and_then(|l| Finger::from_str(&l).ok())
+    }
+}
+
+/// Running state of the properties while walking over the keys, following keyboard-layout-editor's
+/// own serialization rules.
+struct Current {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    angle: f32,
+    rx: f32,
+    ry: f32,
+    decal: bool,
+}
+
+impl Current {
+    fn physical(&self) -> PhysicalKey {
+        let (cx, cy) = (self.x + self.width / 2.0, self.y + self.height / 2.0);
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+
+        PhysicalKey {
+            x: self.rx + (cx - self.rx) * cos - (cy - self.ry) * sin,
+            y: self.ry + (cx - self.rx) * sin + (cy - self.ry) * cos,
+            width: self.width,
+            height: self.height,
+            angle: self.angle,
+        }
+    }
+}
+
+/// Creates a keyboard from keyboard-layout-editor JSON, either as downloaded or as copied from
+/// the raw data tab. Every row of the editor becomes a row of the keyboard.
+///
+/// The column-based default assumes a symmetric board. Keys are assigned by the distance of their
+/// center to the middle of the board: the two innermost 1u columns of each half go to the index
+/// finger, followed by middle, ring, and pinky for all remaining columns. Keys on the bottom row that
+/// are at least 1.5u wide are thumb keys. Anything else should be annotated in the legends.
+pub fn parse(json: &str, assignment: FingerAssignment) -> Result<Keyboard, KleError> {
+    let rows = parse_rows(json)?;
+    let keys = parse_keys(&rows)?;
+
+    if keys.is_empty() {
+        return Err(KleError::NoKeys);
+    }
+
+    let fingers = assign_fingers(&keys, assignment)?;
+
+    let mut shape = Vec::new();
+    for key in keys.iter() {
+        match shape.last_mut() {
+            Some((row, len)) if *row == key.row => *len += 1,
+            _ => shape.push((key.row, 1)),
+        }
+    }
+    let shape = shape.into_iter().map(|(_, len)| len).collect::<Vec<_>>();
+
+    let physical = keys.into_iter().map(|k| k.physical).collect();
+
+    let keyboard = Keyboard::new(&fingers)
+        .with_shape(&shape)
+        .and_then(|k| k.with_physical(physical))
+        .expect("shape and physical keys are taken from the same keys");
+
+    Ok(keyboard)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load<P: AsRef<std::path::Path>>(
+    path: P,
+    assignment: FingerAssignment,
+) -> Result<Keyboard, KleError> {
+    let content = std::fs::read_to_string(path)?;
+    parse(&content, assignment)
+}
+
+fn parse_rows(json: &str) -> Result<Vec<Value>, KleError> {
+    let value = match serde_json::from_str::<Value>(json) {
+        Ok(value) => value,
+        Err(e) => match serde_json::from_str::<Value>(&quote_keys(json)) {
+            Ok(value) => value,
+            Err(_) => match serde_json::from_str::<Value>(&format!("[{}]", quote_keys(json))) {
+                Ok(value) => value,
+                Err(_) => return Err(e.into()),
+            },
+        },
+    };
+
+    match value {
+        // a single row copied from the raw data tab
+        Value::Array(items) if items.iter().any(|i| !i.is_array() && !i.is_object()) => {
+            Ok(vec![Value::Array(items)])
+        }
+        Value::Array(rows) => Ok(rows),
+        _ => Err(KleError::NotAList),
+    }
+}
+
+/// The raw data tab of keyboard-layout-editor leaves property names unquoted, which isn't valid
+/// JSON. This quotes them.
+fn quote_keys(raw: &str) -> String {
+    let mut res = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            res.push(c);
+            match c {
+                '\\' => res.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+            res.push(c);
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut ident = String::from(c);
+            while let Some(&next) = chars.peek() {
+                if next.is_ascii_alphanumeric() || next == '_' {
+                    ident.push(next);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            let rest = chars.clone().find(|c| !c.is_whitespace());
+            if rest == Some(':') {
+                res.push_str(&format!("\"{ident}\""));
+            } else {
+                res.push_str(&ident);
+            }
+        } else {
+            res.push(c);
+        }
+    }
+
+    res
+}
+
+fn parse_keys(rows: &[Value]) -> Result<Vec<KleKey>, KleError> {
+    let mut current = Current {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+        angle: 0.0,
+        rx: 0.0,
+        ry: 0.0,
+        decal: false,
+    };
+
+    let mut keys = Vec::new();
+    let mut row_nr = 0;
+
+    for (i, row) in rows.iter().enumerate() {
+        let items = match row {
+            Value::Array(items) => items,
+            // keyboard metadata like the name of the board
+            Value::Object(_) if i == 0 => continue,
+            _ => return Err(KleError::InvalidRow(i + 1)),
+        };
+
+        row_nr += 1;
+        let mut index = 0;
+
+        for (k, item) in items.iter().enumerate() {
+            match item {
+                Value::String(legend) => {
+                    if !current.decal {
+                        index += 1;
+                        keys.push(KleKey {
+                            physical: current.physical(),
+                            legend: legend.clone(),
+                            row: row_nr,
+                            index,
+                        });
+                    }
+
+                    current.x += current.width;
+                    current.width = 1.0;
+                    current.height = 1.0;
+                    current.decal = false;
+                }
+                Value::Object(props) => {
+                    let num =
+                        |name: &str| props.get(name).and_then(Value::as_f64).map(|v| v as f32);
+
+                    let rotation = ["r", "rx", "ry"].iter().any(|p| props.contains_key(*p));
+                    if rotation && k != 0 {
+                        return Err(KleError::MisplacedRotation(row_nr));
+                    }
+
+                    if let Some(r) = num("r") {
+                        current.angle = r;
+                    }
+                    if let Some(rx) = num("rx") {
+                        current.rx = rx;
+                    }
+                    if let Some(ry) = num("ry") {
+                        current.ry = ry;
+                    }
+                    if props.contains_key("rx") || props.contains_key("ry") {
+                        current.x = current.rx;
+                        current.y = current.ry;
+                    }
+                    if let Some(x) = num("x") {
+                        current.x += x;
+                    }
+                    if let Some(y) = num("y") {
+                        current.y += y;
+                    }
+                    if let Some(w) = num("w") {
+                        current.width = w;
+                    }
+                    if let Some(h) = num("h") {
+                        current.height = h;
+                    }
+                    if let Some(d) = props.get("d").and_then(Value::as_bool) {
+                        current.decal = d;
+                    }
+                }
+                _ => return Err(KleError::InvalidItem(row_nr)),
+            }
+        }
+
+        current.y += 1.0;
+        current.x = current.rx;
+    }
+
+    Ok(keys)
+}
+
+fn assign_fingers(keys: &[KleKey], assignment: FingerAssignment) -> Result<Vec<Finger>, KleError> {
+    use Finger::*;
+
+    let last_row = keys.iter().map(|k| k.row).max().unwrap_or(0);
+    let is_thumb = |k: &KleKey| k.row == last_row && k.physical.width >= 1.5;
+
+    let (min_x, max_x) = keys
+        .iter()
+        .map(|k| k.physical.x)
+        .fold((f32::MAX, f32::MIN), |(min, max), x| {
+            (min.min(x), max.max(x))
+        });
+    let middle = (min_x + max_x) / 2.0;
+
+    // distance of the innermost column of each half to the middle, so split gaps are ignored
+    let inner = |left: bool| {
+        keys.iter()
+            .filter(|k| !is_thumb(k) && (k.physical.x < middle) == left)
+            .map(|k| (k.physical.x - middle).abs())
+            .fold(f32::MAX, f32::min)
+    };
+    let (inner_left, inner_right) = (inner(true), inner(false));
+
+    let column_finger = |k: &KleKey| {
+        let left = k.physical.x < middle;
+
+        if is_thumb(k) {
+            return if left { LT } else { RT };
+        }
+
+        let inner = if left { inner_left } else { inner_right };
+        let column = ((k.physical.x - middle).abs() - inner + 0.5).floor() as i32;
+
+        match (left, column) {
+            (true, ..=1) => LI,
+            (true, 2) => LM,
+            (true, 3) => LR,
+            (true, _) => LP,
+            (false, ..=1) => RI,
+            (false, 2) => RM,
+            (false, 3) => RR,
+            (false, _) => RP,
+        }
+    };
+
+    keys.iter()
+        .map(|k| match (assignment, k.annotated_finger()) {
+            (FingerAssignment::Columns, _) => Ok(column_finger(k)),
+            (_, Some(f)) => Ok(f),
+            (FingerAssignment::Auto, None) => Ok(column_finger(k)),
+            (FingerAssignment::Legends, None) => Err(KleError::MissingFinger {
+                row: k.row,
+                index: k.index,
+                legend: k.legend.replace('\n', " "),
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libdof::dofinitions::Finger::*;
+
+    const SPLIT: &str = r#"[
+        {"name": "split 3x5+2"},
+        ["Q","W","E","R","T",{"x":1},"Y","U","I","O","P"],
+        ["A","S","D","F","G",{"x":1},"H","J","K","L",";"],
+        ["Z","X","C","V","B",{"x":1},"N","M",",",".","/"],
+        [{"x":3},"Space\nLT",{"x":3},"Enter\nRT"]
+    ]"#;
+
+    #[test]
+    fn split_board() {
+        let keyboard = parse(SPLIT, FingerAssignment::Auto).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LT, RT,
+        ];
+
+        assert_eq!(keyboard.fingering(), expected);
+        assert_eq!(keyboard.shape(), [10, 10, 10, 2]);
+
+        let physical = keyboard.physical().unwrap();
+        assert_eq!(physical.len(), 32);
+        assert_eq!((physical[5].x, physical[5].y), (6.5, 0.5));
+        assert_eq!((physical[31].x, physical[31].y), (7.5, 3.5));
+
+        let columns = parse(SPLIT, FingerAssignment::Columns).unwrap();
+        assert_eq!(columns.get_finger(30), Some(LI));
+
+        assert!(matches!(
+            parse(SPLIT, FingerAssignment::Legends),
+            Err(KleError::MissingFinger {
+                row: 1,
+                index: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn raw_data() {
+        let raw = r#"["LP","LI",{x:0.5,w:1.5},"RI\nthumb"],
+            [{r:90,rx:1,ry:1},"LT"]"#;
+
+        let keyboard = parse(raw, FingerAssignment::Legends).unwrap();
+        let physical = keyboard.physical().unwrap();
+
+        assert_eq!(keyboard.fingering(), [LP, LI, RI, LT]);
+        assert_eq!(keyboard.shape(), [3, 1]);
+        assert_eq!(physical[2].width, 1.5);
+        assert!((physical[3].x - 0.5).abs() < 1e-5);
+        assert!((physical[3].y - 1.5).abs() < 1e-5);
+
+        assert!(matches!(
+            parse(r#"[["A",{"r":10},"B"]]"#, FingerAssignment::Auto),
+            Err(KleError::MisplacedRotation(1))
+        ));
+        assert!(matches!(
+            parse("[]", FingerAssignment::Auto),
+            Err(KleError::NoKeys)
+        ));
+    }
+}
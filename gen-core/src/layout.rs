@@ -1,42 +1,173 @@
-use libdof::dofinitions::Finger;
+use std::collections::HashSet;
+
+use libdof::{
+    dofinitions::{Finger, Key, SpecialKey},
+    Dof,
+};
 use nanorand::{Rng, WyRand};
+use thiserror::Error;
+
+use crate::{
+    keyboard::{Hand, Keyboard, Pos},
+    mapping::Mapping,
+    BACKSPACE_CHAR, REPEAT_KEY, SHIFT_CHAR,
+};
+
+/// Key of a position that holds no character. Index 0 of every `Mapping` is `REPLACEMENT_CHAR`,
+/// which never has a frequency, so empty positions never contribute to a score.
+pub const EMPTY_KEY: usize = 0;
+
+/// Names that can be used in the text format for keys that are hard to write down otherwise.
+/// The first name of each key is the one that's used when printing.
+const KEY_NAMES: &[(char, &[&str])] = &[
+    (' ', &["spc", "space"]),
+    ('\n', &["ret", "enter"]),
+    ('\t', &["tab"]),
+    ('~', &["\\~"]),
+    (SHIFT_CHAR, &["shft", "shift"]),
+    (REPEAT_KEY, &["rpt", "repeat"]),
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LayoutError {
+    #[error("Expected {expected} rows, but found {found}")]
+    RowCount { expected: usize, found: usize },
+
+    #[error("Row {row} should have {expected} keys, but has {found}")]
+    RowLength {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
 
-use crate::keyboard::Pos;
+    #[error("'{0}' is not a single character or a known key name")]
+    InvalidKey(String),
+
+    #[error("'{0}' appears on the layout more than once")]
+    DuplicateChar(char),
+
+    #[error("'{0}' does not occur in the corpus")]
+    UnknownChar(char),
+
+    #[error("There are {chars} characters, but only {keys} keys")]
+    TooManyChars { chars: usize, keys: usize },
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Layout {
     keys: Box<[usize]>,
     fingers: Box<[Finger]>,
+    pinned: Box<[bool]>,
 }
 
 impl Layout {
     pub fn new(keys: &[usize], fingering: &[Finger]) -> Option<Self> {
-        if keys.len() == fingering.len() {
-            let keys = keys.to_vec().into();
-            let fingers = fingering.to_vec().into();
-
-            Some(Self { keys, fingers })
-        } else {
-            None
-        }
+        Self::from_vecs(keys.to_vec(), fingering.to_vec())
     }
 
     pub fn from_vecs(keys: Vec<usize>, fingering: Vec<Finger>) -> Option<Self> {
         if keys.len() == fingering.len() {
+            let pinned = vec![false; keys.len()].into();
             let keys = keys.into();
             let fingers = fingering.into();
 
-            Some(Self { keys, fingers })
+            Some(Self {
+                keys,
+                fingers,
+                pinned,
+            })
         } else {
             None
         }
     }
 
-    pub fn random(mut keys: Vec<usize>, fingering: Vec<Finger>) -> Option<Self> {
-        let mut rng = WyRand::new();
+    pub fn random(keys: Vec<usize>, fingering: Vec<Finger>) -> Option<Self> {
+        let mut layout = Self::from_vecs(keys, fingering)?;
+        layout.shuffle();
+
+        Some(layout)
+    }
+
+    /// Randomly redistributes all keys that aren't on a pinned position.
+    pub fn shuffle(&mut self) {
+        self.shuffle_with(&mut WyRand::new());
+    }
+
+    /// Like [`Layout::shuffle`], but always results in the same layout for the same seed.
+    pub fn shuffle_seeded(&mut self, seed: u64) {
+        self.shuffle_with(&mut WyRand::new_seed(seed));
+    }
+
+    fn shuffle_with(&mut self, rng: &mut WyRand) {
+        let free = (0..self.len())
+            .filter(|&p| !self.pinned[p])
+            .collect::<Vec<_>>();
+
+        let mut keys = free.iter().map(|&p| self.keys[p]).collect::<Vec<_>>();
         rng.shuffle(&mut keys);
 
-        Self::from_vecs(keys, fingering)
+        for (p, k) in free.into_iter().zip(keys) {
+            self.keys[p] = k;
+        }
+    }
+
+    /// Pins a position so the optimizer won't move its key. Returns `None` if the position doesn't
+    /// exist.
+    pub fn pin(&mut self, pos: Pos) -> Option<()> {
+        *self.pinned.get_mut(pos)? = true;
+        Some(())
+    }
+
+    pub fn unpin(&mut self, pos: Pos) -> Option<()> {
+        *self.pinned.get_mut(pos)? = false;
+        Some(())
+    }
+
+    /// Swaps the keys on two positions. Returns `None` without swapping if either position doesn't
+    /// exist or is pinned, since a pin keeps its position's key in place.
+    pub fn swap_keys(&mut self, p1: Pos, p2: Pos) -> Option<()> {
+        if p1 < self.len() && p2 < self.len() && !self.pinned[p1] && !self.pinned[p2] {
+            self.keys.swap(p1, p2);
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Empties a position and pins it, so no key can ever be placed there. The key that was on it
+    /// is moved to the first unpinned empty position. Returns `None` without changing anything if
+    /// the position doesn't exist, or if its key has nowhere to go.
+    pub fn block(&mut self, pos: Pos) -> Option<()> {
+        let key = self.key(pos)?;
+
+        if key != EMPTY_KEY {
+            let p = (0..self.len())
+                .find(|&p| p != pos && !self.pinned[p] && self.keys[p] == EMPTY_KEY)?;
+            self.keys[p] = key;
+        }
+
+        self.keys[pos] = EMPTY_KEY;
+        self.pinned[pos] = true;
+
+        Some(())
+    }
+
+    pub fn is_pinned(&self, pos: Pos) -> bool {
+        self.pinned.get(pos).copied().unwrap_or(false)
+    }
+
+    pub fn is_empty_key(&self, pos: Pos) -> bool {
+        self.key(pos) == Some(EMPTY_KEY)
+    }
+
+    /// Replaces the fingering of the layout. Returns `None` if the amount of fingers doesn't match.
+    pub fn set_fingering(&mut self, fingering: &[Finger]) -> Option<()> {
+        if fingering.len() == self.len() {
+            self.fingers = fingering.into();
+            Some(())
+        } else {
+            None
+        }
     }
 
     pub fn fingers(&self) -> &[Finger] {
@@ -104,4 +235,192 @@ impl Layout {
         *self.keys.get_unchecked_mut(p1) = *self.keys.get_unchecked(p2);
         *self.keys.get_unchecked_mut(p2) = help;
     }
+
+    /// Parses a layout in the text format that [`Layout::format`] produces. Every line is a row of
+    /// the keyboard, with keys separated by whitespace. The amount of whitespace doesn't matter, so
+    /// split gaps and the indentation of thumb clusters are only there for readability. `~` is an
+    /// empty position, and whitespace characters can be written as `spc`, `ret` and `tab`.
+    pub fn parse(s: &str, keyboard: &Keyboard, mapping: &Mapping) -> Result<Self, LayoutError> {
+        let rows = s
+            .lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<_>>();
+
+        Self::from_rows(&rows, keyboard, mapping, |token| parse_key(token))
+    }
+
+    /// Reads the main layer of a .dof layout. Its rows have to match the shape of `keyboard`.
+    /// Special keys like space and shift are read as the characters this crate uses for them, and
+    /// keys that don't type anything, like words and layer keys, are left empty.
+    pub fn from_dof(
+        dof: &Dof,
+        keyboard: &Keyboard,
+        mapping: &Mapping,
+    ) -> Result<Self, LayoutError> {
+        let rows = dof.main_layer().rows().collect::<Vec<_>>();
+
+        Self::from_rows(&rows, keyboard, mapping, |key| Ok(dof_key(key)))
+    }
+
+    fn from_rows<T>(
+        rows: &[impl AsRef<[T]>],
+        keyboard: &Keyboard,
+        mapping: &Mapping,
+        key: impl Fn(&T) -> Result<Option<char>, LayoutError>,
+    ) -> Result<Self, LayoutError> {
+        if rows.len() != keyboard.shape().len() {
+            return Err(LayoutError::RowCount {
+                expected: keyboard.shape().len(),
+                found: rows.len(),
+            });
+        }
+
+        for (i, (row, &expected)) in rows.iter().zip(keyboard.shape()).enumerate() {
+            if row.as_ref().len() != expected {
+                return Err(LayoutError::RowLength {
+                    row: i + 1,
+                    expected,
+                    found: row.as_ref().len(),
+                });
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut keys = Vec::with_capacity(keyboard.len());
+
+        for k in rows.iter().flat_map(|r| r.as_ref()) {
+            match key(k)? {
+                None => keys.push(EMPTY_KEY),
+                Some(c) if !seen.insert(c) => return Err(LayoutError::DuplicateChar(c)),
+                Some(c) => keys.push(mapping.get(c).ok_or(LayoutError::UnknownChar(c))?),
+            }
+        }
+
+        Ok(Self::from_vecs(keys, keyboard.fingering().to_vec())
+            .expect("the keyboard shape adds up to the amount of fingers"))
+    }
+
+    /// Creates a .dof layout for `keyboard`, with an explicit fingering.
+    pub fn to_dof(&self, name: &str, keyboard: &Keyboard, mapping: &Mapping) -> Dof {
+        let rows = |key: &dyn Fn(Pos) -> String| {
+            keyboard
+                .rows()
+                .map(|row| row.map(key).collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+        };
+
+        let main = rows(&|p| match format_key(self.keys[p], mapping).as_str() {
+            "*" => "\\*".into(),
+            key => key.into(),
+        });
+        let fingering = rows(&|p| self.fingers[p].to_string());
+
+        let dof = serde_json::json!({
+            "name": name,
+            "board": "custom",
+            "layers": { "main": main },
+            "fingering": fingering,
+        });
+
+        serde_json::from_value(dof).expect("layouts always make valid .dof files")
+    }
+
+    /// The keys of the layout row by row following the shape of `keyboard`, written the way
+    /// [`Layout::parse`] reads them.
+    pub fn rows(&self, keyboard: &Keyboard, mapping: &Mapping) -> Vec<Vec<String>> {
+        keyboard
+            .rows()
+            .map(|row| row.map(|p| format_key(self.keys[p], mapping)).collect())
+            .collect()
+    }
+
+    /// Prints the layout row by row following the shape of `keyboard`. A wider gap separates the
+    /// left and right hand, and rows are indented so their gaps line up.
+    pub fn format(&self, keyboard: &Keyboard, mapping: &Mapping) -> String {
+        let rows = keyboard
+            .rows()
+            .map(|row| {
+                let split = row.clone().skip(1).find(|&p| {
+                    let hand = |p| self.finger(p).and_then(|f| keyboard.hands().hand(f));
+                    hand(p - 1) == Some(Hand::Left) && hand(p) == Some(Hand::Right)
+                });
+
+                let keys = |positions: std::ops::Range<Pos>| {
+                    positions
+                        .map(|p| format_key(self.keys[p], mapping))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+
+                match split {
+                    Some(split) => (keys(row.start..split), Some(keys(split..row.end))),
+                    None => (keys(row), None),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let gap = rows
+            .iter()
+            .filter(|(_, right)| right.is_some())
+            .map(|(left, _)| left.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        rows.into_iter()
+            .map(|(left, right)| match right {
+                Some(right) => format!("{left:>gap$}  {right}"),
+                None => left,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn parse_key(token: &str) -> Result<Option<char>, LayoutError> {
+    if token == "~" {
+        return Ok(None);
+    }
+
+    let lower = token.to_lowercase();
+
+    if let Some((c, _)) = KEY_NAMES
+        .iter()
+        .find(|(_, names)| names.contains(&lower.as_str()))
+    {
+        return Ok(Some(*c));
+    }
+
+    let mut chars = token.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Some(c)),
+        _ => Err(LayoutError::InvalidKey(token.into())),
+    }
+}
+
+fn dof_key(key: &Key) -> Option<char> {
+    match key {
+        Key::Char(c) => Some(*c),
+        Key::Special(SpecialKey::Space) => Some(' '),
+        Key::Special(SpecialKey::Enter) => Some('\n'),
+        Key::Special(SpecialKey::Tab) => Some('\t'),
+        Key::Special(SpecialKey::Shift) => Some(SHIFT_CHAR),
+        Key::Special(SpecialKey::Repeat) => Some(REPEAT_KEY),
+        Key::Special(SpecialKey::Backspace) => Some(BACKSPACE_CHAR),
+        _ => None,
+    }
+}
+
+fn format_key(key: usize, mapping: &Mapping) -> String {
+    if key == EMPTY_KEY {
+        return "~".into();
+    }
+
+    let c = mapping.get_c(key);
+
+    match KEY_NAMES.iter().find(|(k, _)| *k == c) {
+        Some((_, names)) if c.is_whitespace() || c == '~' => names[0].into(),
+        _ => c.into(),
+    }
 }
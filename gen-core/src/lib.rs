@@ -1,10 +1,19 @@
+pub mod analysis;
 pub mod corpus_refiner;
+pub mod corpus_stats;
 pub mod data;
+pub mod fingering;
 pub mod keyboard;
+pub mod keystrokes;
+pub mod kle;
 pub mod layout;
 pub mod mapping;
 pub mod optimizer;
 pub mod prelude;
+pub mod presets;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod project;
+pub mod refiner_config;
 pub mod trigram_types;
 pub mod weights;
 
@@ -13,3 +22,6 @@ pub use libdof;
 pub const REPLACEMENT_CHAR: char = char::REPLACEMENT_CHARACTER;
 pub const SHIFT_CHAR: char = '⇑';
 pub const REPEAT_KEY: char = '@';
+pub const BACKSPACE_CHAR: char = '⌫';
+/// Stands in for the rare characters that were folded together by [`Data::fold_rare_chars`](data::Data::fold_rare_chars).
+pub const OTHER_CHAR: char = '◌';
@@ -33,6 +33,10 @@ impl<S: BuildHasher> Mapping<S> {
         self.0.pop()
     }
 
+    pub fn get(&self, c: char) -> Option<usize> {
+        self.0.get(&c).copied()
+    }
+
     pub fn get_u(&self, c: char) -> usize {
         match self.0.get(&c) {
             Some(c) => *c,
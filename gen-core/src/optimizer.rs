@@ -1,14 +1,47 @@
 use crate::data::Data;
 use itertools::Itertools;
-use libdof::dofinitions::Finger;
-use std::{collections::HashMap, fmt::Write as _, io::Write as _};
+use libdof::{dofinitions::Finger, Dof};
+use nanorand::{Rng, WyRand};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use crate::{
-    keyboard::Pos, layout::Layout, mapping::Mapping, trigram_types::TrigramTypes, weights::Weights,
+    analysis::{Frequency, LayoutAnalysis},
+    keyboard::{Keyboard, Pos},
+    layout::{Layout, LayoutError, EMPTY_KEY},
+    mapping::Mapping,
+    trigram_types::TrigramTypes,
+    weights::Weights,
+    REPLACEMENT_CHAR,
 };
 
 pub type OptimizerTrigrams = (u32, [u8; 3]);
 
+/// The trigrams whose score changes when two positions are swapped.
+type SwapAffectedTrigrams = HashMap<(Pos, Pos), Box<[OptimizerTrigrams]>>;
+
+/// A run of [`Optimizer::generate_batch_with_progress`] that just finished.
+#[derive(Debug, Clone, Copy)]
+pub struct GenerateProgress<'a> {
+    /// Amount of runs that have finished, including this one.
+    pub done: usize,
+    pub runs: usize,
+    pub layout: &'a Layout,
+    pub score: f32,
+}
+
+const FINGERS: [Finger; 10] = {
+    use Finger::*;
+    [LP, LR, LM, LI, LT, RT, RI, RM, RR, RP]
+};
+
 /// The core of layout generation and optimization.
 /// # Examples
 /// Initialize a simple `Optimizer`
@@ -30,47 +63,36 @@ pub type OptimizerTrigrams = (u32, [u8; 3]);
 ///
 /// let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
 ///
-/// let optimizer = Optimizer::new(&types, data, weights);
+/// let optimizer = Optimizer::new(&types, &data, weights);
 /// ```
 /// Create a
 #[derive(Clone)]
 pub struct Optimizer<'a> {
+    keyboard: Keyboard,
     types: Box<[&'a str]>,
-    freqs: Box<[f32]>,
-    swap_list: Box<[(Pos, Pos)]>,
-    swap_affected_trigrams: HashMap<(Pos, Pos), Box<[OptimizerTrigrams]>>,
+    // Don't depend on the fingering, so optimizers for other keyboards share them.
+    freqs: Arc<[f32]>,
+    swap_list: Arc<[(Pos, Pos)]>,
+    swap_affected_trigrams: Arc<SwapAffectedTrigrams>,
     weights: Box<[f32]>,
+    weight_config: Weights,
     mapping: Mapping,
     len: usize,
 }
 
 impl<'a> Optimizer<'a> {
-    pub fn new(trigram_types: &'a TrigramTypes<'a>, trigram_freqs: Data, weights: Weights) -> Self {
-        let mut trigrams = Vec::with_capacity(trigram_types.keyboard().len().pow(3));
-        let mut weight_vec = Vec::new();
-        let mut t_write = String::new();
-
-        for i in 0..trigram_types.keyboard().len() {
-            for j in 0..trigram_types.keyboard().len() {
-                for k in 0..trigram_types.keyboard().len() {
-                    let t = trigram_types.get_type([i, j, k]);
-                    let f = unsafe { trigram_types.keyboard().get_fs([i, j, k]) };
-                    trigrams.push(t.display());
-
-                    let t_weight = weights.get(t.display());
-                    let f_weight = weights.get_finger_trigram(f);
-                    weight_vec.push(t_weight * f_weight);
-
-                    writeln!(&mut t_write, "[{i}, {j}, {k}]: {}", t.display()).unwrap();
-                }
-            }
-        }
+    pub fn new(
+        trigram_types: &'a TrigramTypes<'a>,
+        trigram_freqs: &Data,
+        weights: Weights,
+    ) -> Self {
+        let (trigrams, weight_vec) = Self::type_tables(trigram_types, &weights);
 
         let mapping = trigram_freqs.inner().keys().flatten().collect::<Mapping>();
 
         let mut freqs = vec![0.0; mapping.len().pow(3)];
 
-        for ([c1, c2, c3], f) in trigram_freqs.into_inner() {
+        for (&[c1, c2, c3], f) in trigram_freqs.frequencies() {
             let i1 = mapping.get_u(c1) * mapping.len().pow(2);
             let i2 = mapping.get_u(c2) * mapping.len();
             let i3 = mapping.get_u(c3);
@@ -112,30 +134,89 @@ impl<'a> Optimizer<'a> {
             }
         }
 
-        let mut f = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open("./trigrams.txt")
-            .unwrap();
-
-        f.write_all(t_write.as_bytes()).unwrap();
-
         Self {
-            types: trigrams.into(),
+            keyboard: trigram_types.keyboard().clone(),
+            types: trigrams,
             freqs: freqs.into(),
             swap_list: swap_list.into(),
-            swap_affected_trigrams,
-            weights: weight_vec.into(),
+            swap_affected_trigrams: Arc::new(swap_affected_trigrams),
+            weights: weight_vec,
+            weight_config: weights,
             mapping,
             len: trigram_types.keyboard().len(),
         }
     }
 
+    /// Classifies and weighs every trigram of positions on the keyboard. These are the only
+    /// tables that depend on the fingering of the keyboard.
+    fn type_tables<'b>(
+        trigram_types: &'b TrigramTypes<'b>,
+        weights: &Weights,
+    ) -> (Box<[&'b str]>, Box<[f32]>) {
+        let mut trigrams = Vec::with_capacity(trigram_types.keyboard().len().pow(3));
+        let mut weight_vec = Vec::with_capacity(trigram_types.keyboard().len().pow(3));
+
+        for i in 0..trigram_types.keyboard().len() {
+            for j in 0..trigram_types.keyboard().len() {
+                for k in 0..trigram_types.keyboard().len() {
+                    let t = trigram_types.get_type([i, j, k]);
+                    let f = unsafe { trigram_types.keyboard().get_fs([i, j, k]) };
+                    trigrams.push(t.display());
+
+                    let t_weight = weights.get(t.display());
+                    let f_weight = weights.get_finger_trigram(f);
+                    weight_vec.push(t_weight * f_weight);
+                }
+            }
+        }
+
+        (trigrams.into(), weight_vec.into())
+    }
+
+    /// Creates an optimizer for a different keyboard with the same amount of keys, reusing the
+    /// corpus frequencies and swap tables of this one. Returns `None` if the amount of keys differs.
+    pub fn with_keyboard<'b>(
+        &self,
+        trigram_types: &'b TrigramTypes<'b>,
+        weights: &Weights,
+    ) -> Option<Optimizer<'b>> {
+        if trigram_types.keyboard().len() != self.len {
+            return None;
+        }
+
+        let (types, table) = Self::type_tables(trigram_types, weights);
+
+        Some(Optimizer {
+            keyboard: trigram_types.keyboard().clone(),
+            types,
+            freqs: self.freqs.clone(),
+            swap_list: self.swap_list.clone(),
+            swap_affected_trigrams: self.swap_affected_trigrams.clone(),
+            weights: table,
+            weight_config: weights.clone(),
+            mapping: self.mapping.clone(),
+            len: self.len,
+        })
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
 
+    pub fn keyboard(&self) -> &Keyboard {
+        &self.keyboard
+    }
+
+    /// The weights the optimizer was created with.
+    pub fn weights(&self) -> &Weights {
+        &self.weight_config
+    }
+
+    /// The characters of the corpus, which keys of a [`Layout`] refer to.
+    pub fn mapping(&self) -> &Mapping {
+        &self.mapping
+    }
+
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -183,22 +264,36 @@ impl<'a> Optimizer<'a> {
             .unwrap_or_else(|| panic!("[{i1}, {i2}, {i3}] is not a valid set of positions"))
     }
 
-    pub fn layout(&self, chars: &[char], fingers: &[Finger]) -> Option<Layout> {
-        let keys = chars
-            .iter()
-            .map(|c| self.mapping.get_u(*c))
-            .collect::<Vec<_>>();
+    /// Creates a layout from a set of characters. If there are fewer characters than fingers, the
+    /// remaining positions are left empty. Every character has to occur in the corpus, and only
+    /// once.
+    pub fn layout(&self, chars: &[char], fingers: &[Finger]) -> Result<Layout, LayoutError> {
+        if chars.len() > fingers.len() {
+            return Err(LayoutError::TooManyChars {
+                chars: chars.len(),
+                keys: fingers.len(),
+            });
+        }
+
+        let mut seen = HashSet::new();
+        let mut keys = Vec::with_capacity(fingers.len());
+
+        for &c in chars {
+            if !seen.insert(c) {
+                return Err(LayoutError::DuplicateChar(c));
+            }
+            keys.push(self.mapping.get(c).ok_or(LayoutError::UnknownChar(c))?);
+        }
+        keys.resize(fingers.len(), EMPTY_KEY);
 
-        Layout::from_vecs(keys, fingers.into())
+        Ok(Layout::from_vecs(keys, fingers.into()).expect("there are as many keys as fingers"))
     }
 
     pub fn random_layout(&self, chars: &[char], fingers: &[Finger]) -> Option<Layout> {
-        let keys = chars
-            .iter()
-            .map(|c| self.mapping.get_u(*c))
-            .collect::<Vec<_>>();
+        let mut layout = self.layout(chars, fingers).ok()?;
+        layout.shuffle();
 
-        Layout::random(keys, fingers.into())
+        Some(layout)
     }
 
     pub fn affected_trigrams(&self, p1: Pos, p2: Pos) -> &[OptimizerTrigrams] {
@@ -215,13 +310,85 @@ impl<'a> Optimizer<'a> {
 
                     let ttype = self.get_t([i, j, k]);
                     let freq = self.get_f(keys);
-                    res.entry(ttype).and_modify(|f| *f += freq).or_default();
+                    *res.entry(ttype).or_default() += freq;
                 }
             }
         }
         res
     }
 
+    /// Percentage of key presses made by each finger of the layout, ordered from the left pinky to
+    /// the right pinky. Fingers without keys are left out.
+    pub fn finger_usage(&self, layout: &Layout) -> Vec<(Finger, f32)> {
+        let m = self.mapping.len();
+        let mut usage = [None::<f32>; 10];
+
+        for (&key, &finger) in layout.keys().iter().zip(layout.fingers()) {
+            let freq = self.freqs[key * m * m..(key + 1) * m * m]
+                .iter()
+                .sum::<f32>();
+            *usage[finger as usize].get_or_insert(0.0) += freq;
+        }
+
+        usage
+            .into_iter()
+            .zip(FINGERS)
+            .filter_map(|(freq, finger)| Some((finger, freq?)))
+            .collect()
+    }
+
+    /// The `n` characters of the corpus that are typed most often, most common first.
+    pub fn most_common_chars(&self, n: usize) -> Vec<char> {
+        let m = self.mapping.len();
+
+        let mut chars = (0..m)
+            .map(|key| {
+                let freq = self.freqs[key * m * m..(key + 1) * m * m]
+                    .iter()
+                    .sum::<f32>();
+                (self.mapping.get_c(key), freq)
+            })
+            .filter(|&(c, freq)| c != REPLACEMENT_CHAR && freq > 0.0)
+            .collect::<Vec<_>>();
+
+        chars.sort_by(|(c1, f1), (c2, f2)| f2.total_cmp(f1).then(c1.cmp(c2)));
+        chars.into_iter().take(n).map(|(c, _)| c).collect()
+    }
+
+    /// Scores a layout and collects its trigram types and finger usage.
+    pub fn analyze(&self, name: &str, layout: &Layout) -> LayoutAnalysis {
+        let mut trigram_types = self
+            .calc_trigram_types(layout)
+            .into_iter()
+            .map(|(name, percentage)| Frequency {
+                name: name.into(),
+                percentage,
+            })
+            .collect::<Vec<_>>();
+        trigram_types.sort_by(|f1, f2| {
+            (f2.percentage)
+                .total_cmp(&f1.percentage)
+                .then_with(|| f1.name.cmp(&f2.name))
+        });
+
+        let finger_usage = self
+            .finger_usage(layout)
+            .into_iter()
+            .map(|(finger, percentage)| Frequency {
+                name: finger.to_string(),
+                percentage,
+            })
+            .collect();
+
+        LayoutAnalysis {
+            name: name.into(),
+            rows: layout.rows(&self.keyboard, &self.mapping),
+            score: self.calc_score(layout),
+            trigram_types,
+            finger_usage,
+        }
+    }
+
     pub fn calc_score(&self, layout: &Layout) -> f32 {
         let mut res = 0.0;
 
@@ -245,6 +412,10 @@ impl<'a> Optimizer<'a> {
         let mut best_score = self.calc_score(layout);
 
         for (p1, p2) in self.swap_list.iter().copied() {
+            if layout.is_pinned(p1) || layout.is_pinned(p2) {
+                continue;
+            }
+
             unsafe { layout.swap(p1, p2) };
 
             let new_score = self.calc_score(layout);
@@ -285,6 +456,10 @@ impl<'a> Optimizer<'a> {
         let mut best_score = cache.total;
 
         for (p1, p2) in self.swap_list.iter().copied() {
+            if layout.is_pinned(p1) || layout.is_pinned(p2) {
+                continue;
+            }
+
             let original_score = cache.total;
 
             unsafe { layout.swap(p1, p2) };
@@ -346,6 +521,84 @@ impl<'a> Optimizer<'a> {
         Some(layout)
     }
 
+    /// Shuffles all unpinned keys of `template` and optimizes the result. Pinned and blocked
+    /// positions keep their key.
+    pub fn generate_from(&self, template: &Layout) -> Layout {
+        let mut layout = template.clone();
+        layout.shuffle();
+
+        self.optimize(&mut layout);
+
+        layout
+    }
+
+    /// Like [`Optimizer::generate_from`], but always results in the same layout for the same seed.
+    pub fn generate_seeded(&self, template: &Layout, seed: u64) -> Layout {
+        let mut layout = template.clone();
+        layout.shuffle_seeded(seed);
+
+        self.optimize(&mut layout);
+
+        layout
+    }
+
+    /// Generates `runs` layouts from `template` in parallel, and returns them with their scores
+    /// from best to worst. With a seed, run `i` uses `seed + i`, so the result doesn't depend on
+    /// the amount of threads.
+    pub fn generate_batch(
+        &self,
+        template: &Layout,
+        runs: usize,
+        seed: Option<u64>,
+    ) -> Vec<(Layout, f32)> {
+        self.generate_batch_with_progress(template, runs, seed, |_| ControlFlow::Continue(()))
+    }
+
+    /// Like [`Optimizer::generate_batch`], but calls `progress` every time a run finishes. Once it
+    /// returns [`ControlFlow::Break`], runs that haven't started yet are skipped, and only the
+    /// layouts that were finished are returned.
+    pub fn generate_batch_with_progress<F>(
+        &self,
+        template: &Layout,
+        runs: usize,
+        seed: Option<u64>,
+        progress: F,
+    ) -> Vec<(Layout, f32)>
+    where
+        F: Fn(GenerateProgress) -> ControlFlow<()> + Sync,
+    {
+        let seed = seed.unwrap_or_else(|| WyRand::new().generate());
+        let done = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+
+        let mut layouts = (0..runs as u64)
+            .into_par_iter()
+            .filter_map(|i| {
+                if stop.load(Ordering::Relaxed) {
+                    return None;
+                }
+
+                let layout = self.generate_seeded(template, seed.wrapping_add(i));
+                let score = self.calc_score(&layout);
+
+                let update = GenerateProgress {
+                    done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                    runs,
+                    layout: &layout,
+                    score,
+                };
+                if progress(update).is_break() {
+                    stop.store(true, Ordering::Relaxed);
+                }
+
+                Some((layout, score))
+            })
+            .collect::<Vec<_>>();
+
+        layouts.sort_by(|(_, s1), (_, s2)| s2.total_cmp(s1));
+        layouts
+    }
+
     fn new_cache(&self, layout: &Layout) -> Cache {
         let mut buf = Vec::new();
 
@@ -365,27 +618,25 @@ impl<'a> Optimizer<'a> {
         Cache::new(buf)
     }
 
-    pub fn layout_to_str(&self, layout: &Layout) -> String {
-        use libdof::dofinitions::Finger::*;
+    /// Returns the position of a character on a layout, if it's on it.
+    pub fn position(&self, layout: &Layout, c: char) -> Option<Pos> {
+        let key = self.mapping.get(c)?;
+        layout.keys().iter().position(|&k| k == key)
+    }
 
-        let mut last_finger = RP;
-        let mut res = String::new();
+    /// Parses a layout for the keyboard of this optimizer. See [`Layout::parse`] for the format.
+    pub fn parse_layout(&self, s: &str) -> Result<Layout, LayoutError> {
+        Layout::parse(s, &self.keyboard, &self.mapping)
+    }
 
-        layout
-            .keys()
-            .iter()
-            .zip(layout.fingers())
-            .for_each(|(k, f)| {
-                match (last_finger, f) {
-                    (RP, LP) => res.push('\n'),
-                    (LI, RI) => res.push_str("  "),
-                    _ => res.push(' '),
-                };
-                res.push(self.mapping.get_c(*k));
-                last_finger = *f;
-            });
+    /// Reads the main layer of a .dof layout for the keyboard of this optimizer. See
+    /// [`Layout::from_dof`].
+    pub fn layout_from_dof(&self, dof: &Dof) -> Result<Layout, LayoutError> {
+        Layout::from_dof(dof, &self.keyboard, &self.mapping)
+    }
 
-        res
+    pub fn layout_to_str(&self, layout: &Layout) -> String {
+        layout.format(&self.keyboard, &self.mapping)
     }
 
     pub fn print_layout(&self, name: &str, layout: &Layout) {
@@ -393,7 +644,7 @@ impl<'a> Optimizer<'a> {
         let score = self.calc_score(layout);
         let freqs = self.calc_trigram_types(layout);
 
-        println!("{name}: {score}{layout_str}\n");
+        println!("{name}: {score}\n{layout_str}\n");
 
         for (ttype, freq) in freqs {
             println!("{:<15}{:>6}%", format!("{ttype}:"), format!("{freq:.3}"))
@@ -448,21 +699,22 @@ mod tests {
 
         let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
 
-        let optimizer = Optimizer::new(&types, data, weights);
+        let optimizer = Optimizer::new(&types, &data, weights);
 
-        let chars = r#"
-            r t p w g  f z , a u
-            l d c s b  h k i o / e
-            n m y v q  ' x j . ;
-        "#
-        .split_whitespace()
-        .map(|s| s.chars().next().unwrap())
-        .collect::<Vec<_>>();
+        let template = optimizer
+            .parse_layout(
+                r#"
+                r t p w g  f z , a u
+                l d c s b  h k i o / e
+                n m y v q  ' x j . ;
+                "#,
+            )
+            .unwrap();
 
         let mut layouts = Vec::new();
 
         for _ in 0..50 {
-            let layout = time!(optimizer.generate(&chars, &fingers)).unwrap();
+            let layout = time!(optimizer.generate_from(&template));
             let score = optimizer.calc_score(&layout);
             layouts.push((layout, score));
         }
@@ -491,7 +743,7 @@ mod tests {
 
         let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
 
-        let optimizer = Optimizer::new(&types, data, weights);
+        let optimizer = Optimizer::new(&types, &data, weights);
 
         for t in optimizer.affected_trigrams(0, 11) {
             println!("{t:?}");
@@ -517,18 +769,17 @@ mod tests {
 
         let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
 
-        let optimizer = Optimizer::new(&types, data, weights);
+        let optimizer = Optimizer::new(&types, &data, weights);
 
-        let qwerty_optimal = r#"
-            r t p w g  f z , a u
-            l d c s b  h k i o / e
-            n m y v q  ' x j . ;
-        "#
-        .split_whitespace()
-        .map(|s| s.chars().next().unwrap())
-        .collect::<Vec<_>>();
-
-        let qwerty_optimal = optimizer.layout(&qwerty_optimal, &fingering).unwrap();
+        let qwerty_optimal = optimizer
+            .parse_layout(
+                r#"
+                r t p w g  f z , a u
+                l d c s b  h k i o / e
+                n m y v q  ' x j . ;
+                "#,
+            )
+            .unwrap();
 
         optimizer.print_layout("qwerty_optimal", &qwerty_optimal);
     }
@@ -553,28 +804,27 @@ mod tests {
 
         let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
 
-        let optimizer = Optimizer::new(&types, data, weights);
-
-        let stronk = r#"
-            f d l b v  j g o u ,
-            s t r n k  y m a e i -
-            z q x h p  w c ' ; .
-        "#
-        .split_whitespace()
-        .map(|s| s.chars().next().unwrap())
-        .collect::<Vec<_>>();
-
-        let qwerty = r#"
-            q w e r t  y u i o p
-            a s d f g  h j k l ; '
-            z x c v b  n m , . /
-        "#
-        .split_whitespace()
-        .map(|s| s.chars().next().unwrap())
-        .collect::<Vec<_>>();
-
-        let mut stronk = optimizer.layout(&stronk, &fingering).unwrap();
-        let mut qwerty = optimizer.layout(&qwerty, &fingering).unwrap();
+        let optimizer = Optimizer::new(&types, &data, weights);
+
+        let mut stronk = optimizer
+            .parse_layout(
+                r#"
+                f d l b v  j g o u ,
+                s t r n k  y m a e i -
+                z q x h p  w c ' ; .
+                "#,
+            )
+            .unwrap();
+
+        let mut qwerty = optimizer
+            .parse_layout(
+                r#"
+                q w e r t  y u i o p
+                a s d f g  h j k l ; '
+                z x c v b  n m , . /
+                "#,
+            )
+            .unwrap();
 
         let stronk_score = optimizer.calc_score(&stronk);
         let qwerty_score = optimizer.calc_score(&qwerty);
@@ -607,6 +857,315 @@ mod tests {
         optimizer.print_layout("qwerty optimized", &qwerty);
     }
 
+    #[test]
+    fn unused_positions() {
+        use crate::{keyboard::Keyboard, layout::EMPTY_KEY};
+        use libdof::dofinitions::Finger::*;
+
+        #[rustfmt::skip]
+        let fingering = [
+            LP, LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP, RP,
+            LP, LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP, RP,
+            LP, LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP, RP,
+        ];
+
+        let keyboard = Keyboard::new(&fingering);
+        let types = TrigramTypes::with_defaults(keyboard);
+
+        let data = Data::load("../data/shai.json").expect("couldn't load read data");
+
+        let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
+
+        let optimizer = Optimizer::new(&types, &data, weights);
+
+        let chars = "abcdefghijklmnopqrstuvwxyz,.';".chars().collect::<Vec<_>>();
+
+        let mut template = optimizer.layout(&chars, &fingering).unwrap();
+        template.block(0).unwrap();
+        template.block(24).unwrap();
+
+        let layout = optimizer.generate_from(&template);
+
+        assert!(layout.is_empty_key(0) && layout.is_empty_key(24));
+        assert_eq!(layout.keys().iter().filter(|&&k| k == EMPTY_KEY).count(), 6);
+
+        let mut placed = layout
+            .keys()
+            .iter()
+            .filter(|&&k| k != EMPTY_KEY)
+            .copied()
+            .collect::<Vec<_>>();
+        placed.sort();
+        placed.dedup();
+
+        assert_eq!(placed.len(), chars.len());
+        assert_eq!(
+            optimizer.layout(&[' '; 37], &fingering),
+            Err(LayoutError::TooManyChars {
+                chars: 37,
+                keys: 36
+            })
+        );
+        assert_eq!(
+            optimizer.layout(&['a', 'b', 'a'], &fingering),
+            Err(LayoutError::DuplicateChar('a'))
+        );
+        assert_eq!(
+            optimizer.layout(&['a', '€'], &fingering),
+            Err(LayoutError::UnknownChar('€'))
+        );
+
+        // On a full layout the key of a blocked position can't go anywhere.
+        let keys = (1..=fingering.len()).collect::<Vec<_>>();
+        let mut full = Layout::new(&keys, &fingering).unwrap();
+        let before = full.clone();
+
+        assert_eq!(full.block(0), None);
+        assert_eq!(full, before);
+    }
+
+    #[test]
+    fn layout_format() {
+        use crate::{keyboard::Keyboard, layout::LayoutError, SHIFT_CHAR};
+        use libdof::dofinitions::Finger::*;
+
+        #[rustfmt::skip]
+        let fingering = [
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LT, RT,
+        ];
+
+        let keyboard = Keyboard::new(&fingering);
+        let types = TrigramTypes::with_defaults(keyboard);
+
+        let mut data = Data::load("../data/shai.json").expect("couldn't load read data");
+        let count = data.total / 100;
+        data.trigrams.insert([' ', SHIFT_CHAR, 'e'], count);
+        data.total += count;
+
+        let optimizer = Optimizer::new(&types, &data, Weights::default());
+
+        let layout_str = [
+            "f d l b v  j g o u ,",
+            "s t r n k  y m a e i -",
+            "z q x h p  w c ' ; .",
+            "      spc  ⇑",
+        ]
+        .join("\n");
+
+        let layout = optimizer.parse_layout(&layout_str).unwrap();
+
+        assert_eq!(optimizer.layout_to_str(&layout), layout_str);
+        assert_eq!(
+            optimizer.parse_layout(&layout_str.replace("  ", " ")),
+            Ok(layout)
+        );
+
+        let empty = optimizer
+            .parse_layout(&layout_str.replace("spc", "~"))
+            .unwrap();
+        assert!(empty.is_empty_key(31));
+
+        assert_eq!(
+            optimizer.parse_layout("a b c"),
+            Err(LayoutError::RowCount {
+                expected: 4,
+                found: 1
+            })
+        );
+        assert_eq!(
+            optimizer.parse_layout(&layout_str.replace(" -", "")),
+            Err(LayoutError::RowLength {
+                row: 2,
+                expected: 11,
+                found: 10
+            })
+        );
+        assert_eq!(
+            optimizer.parse_layout(&layout_str.replace('f', "d")),
+            Err(LayoutError::DuplicateChar('d'))
+        );
+        assert_eq!(
+            optimizer.parse_layout(&layout_str.replace('f', "ß")),
+            Err(LayoutError::UnknownChar('ß'))
+        );
+        assert_eq!(
+            optimizer.parse_layout(&layout_str.replace('f', "ff")),
+            Err(LayoutError::InvalidKey("ff".into()))
+        );
+    }
+
+    #[test]
+    fn trigram_type_totals() {
+        use crate::keyboard::Keyboard;
+        use assert_approx_eq::assert_approx_eq;
+
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[3]));
+        let optimizer = Optimizer::new(&types, &Data::from("aaaabc"), Weights::default());
+        let layout = optimizer
+            .layout(&['a', 'b', 'c'], optimizer.keyboard().fingering())
+            .unwrap();
+
+        // Half of the trigrams are `aaa`, which is on the very first position that's counted.
+        let totals = optimizer.calc_trigram_types(&layout);
+        assert_approx_eq!(totals.values().sum::<f32>(), 100.0, 0.01);
+    }
+
+    #[test]
+    fn analysis() {
+        use crate::keyboard::Keyboard;
+        use libdof::dofinitions::Finger::*;
+
+        #[rustfmt::skip]
+        let fingering = [
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP, RP,
+            LP, LR, LM, LI, LI,  RI, RI, RM, RR, RP,
+            LT, RT,
+        ];
+
+        let keyboard = Keyboard::from_shape(&[10, 11, 10, 2]);
+        assert_eq!(keyboard.fingering(), fingering);
+
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+        let data = Data::load("../data/shai.json").expect("couldn't load read data");
+        let optimizer = Optimizer::new(&types, &data, Weights::default());
+
+        let dof = serde_json::from_str::<Dof>(
+            r#"{
+                "name": "Qwerty",
+                "board": "ortho",
+                "layers": {
+                    "main": [
+                        "q w e r t  y u i o p",
+                        "a s d f g  h j k l ;",
+                        "z x c v b  n m , . /"
+                    ]
+                },
+                "fingering": "traditional"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(Keyboard::from_dof(&dof).shape(), [10, 10, 10]);
+
+        let layout = optimizer.layout_from_dof(&dof).unwrap();
+        assert_eq!(
+            optimizer.layout_to_str(&layout),
+            optimizer
+                .parse_layout("q w e r t y u i o p\na s d f g h j k l ;\nz x c v b n m , . /")
+                .map(|l| optimizer.layout_to_str(&l))
+                .unwrap()
+        );
+
+        let usage = optimizer.finger_usage(&layout);
+        assert_eq!(usage.len(), 8);
+        assert_eq!(usage[0].0, LP);
+        assert!(usage[3].1 > usage[0].1);
+
+        let types = optimizer.calc_trigram_types(&layout);
+        let total = types.values().map(|&f| f as f64).sum::<f64>();
+        let total_usage = usage.iter().map(|&(_, f)| f as f64).sum::<f64>();
+        assert!(total <= 100.001 && total_usage <= 100.001);
+        assert!(total < total_usage && total_usage > 90.0);
+
+        let analysis = optimizer.analyze("Qwerty", &layout);
+        assert_eq!(analysis.rows[1][9], ";");
+        assert_eq!(analysis.score, optimizer.calc_score(&layout));
+        assert_eq!(analysis.finger_usage[0].name, "LP");
+        assert!(analysis
+            .trigram_types
+            .windows(2)
+            .all(|w| w[0].percentage >= w[1].percentage));
+        assert_eq!(analysis.trigram_type("Sfb"), types["Sfb"]);
+
+        let json = serde_json::to_value(&analysis).unwrap();
+        assert_eq!(json["name"], "Qwerty");
+        assert_eq!(json["rows"][0][0], "q");
+        assert!(json["trigram_types"][0]["percentage"].is_number());
+    }
+
+    #[test]
+    fn seeded() {
+        use crate::keyboard::Keyboard;
+
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+        let data = Data::load("../data/shai.json").expect("couldn't load read data");
+        let weights = Weights::load("./weights.toml").expect("Couldn't read weights");
+        let optimizer = Optimizer::new(&types, &data, weights);
+
+        let chars = "abcdefghijklmnopqrstuvwxyz',.;".chars().collect::<Vec<_>>();
+        let mut template = optimizer
+            .layout(&chars, optimizer.keyboard().fingering())
+            .unwrap();
+        template.pin(0);
+
+        let batch = optimizer.generate_batch(&template, 4, Some(7));
+
+        assert_eq!(batch.len(), 4);
+        assert!(batch.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(batch.iter().all(|(l, _)| l.key(0) == template.key(0)));
+        assert!(batch
+            .iter()
+            .any(|(l, _)| *l == optimizer.generate_seeded(&template, 9)));
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let stopped = pool.install(|| {
+            optimizer.generate_batch_with_progress(&template, 20, Some(7), |p| match p.done {
+                3 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            })
+        });
+
+        assert_eq!(stopped.len(), 3);
+        assert!(stopped.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let layout = &batch[0].0;
+        let dof = layout.to_dof("best", optimizer.keyboard(), optimizer.mapping());
+
+        assert_eq!(dof.name(), "best");
+        assert_eq!(
+            optimizer.layout_from_dof(&dof).unwrap().keys(),
+            layout.keys()
+        );
+    }
+
+    #[test]
+    fn swap() {
+        use crate::keyboard::Keyboard;
+
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+        let data = Data::load("../data/shai.json").expect("couldn't load read data");
+        let optimizer = Optimizer::new(&types, &data, Weights::default());
+
+        let chars = "abcdefghijklmnopqrstuvwxyz',.;".chars().collect::<Vec<_>>();
+        let mut layout = optimizer
+            .layout(&chars, optimizer.keyboard().fingering())
+            .unwrap();
+
+        assert_eq!(optimizer.position(&layout, 'a'), Some(0));
+        assert_eq!(optimizer.position(&layout, 'e'), Some(4));
+        assert_eq!(optimizer.position(&layout, '€'), None);
+
+        layout.swap_keys(0, 4).unwrap();
+
+        assert_eq!(optimizer.position(&layout, 'a'), Some(4));
+        assert_eq!(optimizer.position(&layout, 'e'), Some(0));
+        assert!(layout.swap_keys(0, 30).is_none());
+
+        layout.pin(4);
+        let before = layout.clone();
+
+        assert!(layout.swap_keys(0, 4).is_none());
+        assert!(layout.swap_keys(4, 1).is_none());
+        assert_eq!(layout, before);
+    }
+
     #[test]
     fn thing() {
         use crate::{keyboard::Keyboard, REPLACEMENT_CHAR};
@@ -624,18 +1183,17 @@ mod tests {
 
         let data = Data::load("../data/shai.json").expect("couldn't load read data");
 
-        let optimizer = Optimizer::new(&types, data, Weights::default());
-
-        let stronk = r#"
-            f d l b v  j g o u ,
-            s t r n k  y m a e i
-            z q x h p  w c ' ; .
-        "#
-        .split_whitespace()
-        .map(|s| s.chars().next().unwrap())
-        .collect::<Vec<_>>();
+        let optimizer = Optimizer::new(&types, &data, Weights::default());
 
-        let layout = optimizer.layout(&stronk, &fingering).unwrap();
+        let layout = optimizer
+            .parse_layout(
+                r#"
+                f d l b v  j g o u ,
+                s t r n k  y m a e i
+                z q x h p  w c ' ; .
+                "#,
+            )
+            .unwrap();
 
         let t1 = ['a', 'b', 'c'];
         let t2 = ['t', 'h', 'e'];
@@ -1,10 +1,18 @@
 pub use crate::{
+    analysis::{Frequency, LayoutAnalysis, NgramFrequency, SCHEMA_VERSION},
     corpus_refiner::{CorpusRefiner, RefineCorpus},
-    data::{Data, DataError},
-    keyboard::Keyboard,
-    layout::Layout,
+    corpus_stats::{CorpusProgress, CorpusStats},
+    data::{BlendSource, Data, DataError, DataMeta, PruneReport},
+    fingering::{FingeringOptions, FingeringResult},
+    keyboard::{Hand, HandModel, Keyboard},
+    keystrokes::KeystrokeOptions,
+    layout::{Layout, LayoutError},
     libdof::dofinitions::Finger,
-    optimizer::Optimizer,
+    optimizer::{GenerateProgress, Optimizer},
+    refiner_config::RefinerConfig,
     trigram_types::{DynamicType, TrigramType, TrigramTypes},
-    weights::Weights,
+    weights::{Weights, WeightsError, WeightsWarning},
 };
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::project::{KeyboardSource, LayoutFile, Project, ProjectError, Source};
@@ -0,0 +1,158 @@
+//! Built-in refiner configs for a couple of languages. Every preset keeps the letters that have
+//! their own key on that language's usual keyboard, types the remaining accented letters through
+//! dead keys and normalizes the local quotation marks.
+
+use indexmap::IndexMap;
+
+use crate::{
+    refiner_config::{DeadKeyConfig, RefinerConfig, SequenceConfig},
+    SHIFT_CHAR,
+};
+
+/// Names of all presets, as accepted by [`refiner_preset`].
+pub const REFINER_PRESETS: &[&str] = &[
+    "english",
+    "norwegian",
+    "german",
+    "french",
+    "spanish",
+    "dutch",
+];
+
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// Returns the refiner config for a language, by its English name or its ISO 639-1 code.
+pub fn refiner_preset(name: &str) -> Option<RefinerConfig> {
+    let config = match name.to_lowercase().as_str() {
+        "english" | "en" => base(""),
+        "norwegian" | "bokmal" | "bokmål" | "no" | "nb" => RefinerConfig {
+            dead_keys: vec![
+                dead_key('´', "áéíóú", true),
+                dead_key('`', "àèòù", true),
+                dead_key('^', "âêô", true),
+                dead_key('¨', "äëïöü", true),
+            ],
+            sequences: quotes("«»„“”"),
+            ..base("æøå")
+        },
+        "german" | "de" => RefinerConfig {
+            dead_keys: vec![
+                dead_key('´', "áéíóú", true),
+                dead_key('`', "àèù", true),
+                dead_key('^', "âêîôû", true),
+            ],
+            sequences: quotes("„“”«»‚"),
+            ..base("äöüß")
+        },
+        "french" | "fr" => RefinerConfig {
+            dead_keys: vec![dead_key('^', "âêîôû", true), dead_key('¨', "ëïüÿ", true)],
+            sequences: [sequence("œ", "oe", true), sequence("æ", "ae", true)]
+                .into_iter()
+                .chain(quotes("«»“”"))
+                .collect(),
+            ..base("éèàùç")
+        },
+        "spanish" | "es" => RefinerConfig {
+            dead_keys: vec![dead_key('´', "áéíóú", true), dead_key('¨', "ü", true)],
+            sequences: quotes("«»“”"),
+            ..base("ñ¿¡")
+        },
+        "dutch" | "nl" => RefinerConfig {
+            dead_keys: vec![
+                dead_key('´', "áéíóú", true),
+                dead_key('`', "àè", true),
+                dead_key('¨', "ëïöü", true),
+            ],
+            sequences: [sequence("ĳ", "ij", true)]
+                .into_iter()
+                .chain(quotes("„“”"))
+                .collect(),
+            ..base("")
+        },
+        _ => return None,
+    };
+
+    Some(config)
+}
+
+fn base(extra_letters: &str) -> RefinerConfig {
+    RefinerConfig {
+        include: format!("{LETTERS}{extra_letters}"),
+        uppercase: true,
+        space: true,
+        qwerty_punct_casings: true,
+        normalize_punct: true,
+        ..Default::default()
+    }
+}
+
+/// Types every accented letter as `key` followed by the letter without its accent.
+fn dead_key(key: char, accented: &str, uppercase: bool) -> DeadKeyConfig {
+    let chars = accented
+        .chars()
+        .map(|c| {
+            let base = c
+                .to_string()
+                .replace(['á', 'à', 'â', 'ä'], "a")
+                .replace(['é', 'è', 'ê', 'ë'], "e")
+                .replace(['í', 'ì', 'î', 'ï'], "i")
+                .replace(['ó', 'ò', 'ô', 'ö'], "o")
+                .replace(['ú', 'ù', 'û', 'ü'], "u")
+                .replace('ÿ', "y");
+
+            (c, base.chars().next().unwrap_or(c))
+        })
+        .collect::<IndexMap<_, _>>();
+
+    DeadKeyConfig {
+        key,
+        chars,
+        uppercase,
+    }
+}
+
+/// Quotation marks that are typed like a double quote, which is shift and `'` on qwerty.
+fn quotes(marks: &str) -> Vec<SequenceConfig> {
+    marks
+        .chars()
+        .map(|c| sequence(&c.to_string(), &format!("{SHIFT_CHAR}'"), false))
+        .collect()
+}
+
+fn sequence(from: &str, to: &str, uppercase: bool) -> SequenceConfig {
+    SequenceConfig {
+        from: from.into(),
+        to: to.into(),
+        uppercase,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus_refiner::RefineCorpus;
+
+    #[test]
+    fn presets() {
+        for name in REFINER_PRESETS {
+            assert!(refiner_preset(name).is_some(), "{name}");
+        }
+        assert_eq!(refiner_preset("nb"), refiner_preset("Norwegian"));
+        assert_eq!(refiner_preset("klingon"), None);
+
+        let refine = |preset: &str, text: &str| {
+            let refiner = refiner_preset(preset).unwrap().build();
+            text.chars()
+                .refine(&refiner)
+                .flatten()
+                .filter(|&c| c != crate::REPLACEMENT_CHAR)
+                .collect::<String>()
+        };
+
+        assert_eq!(refine("norwegian", "Kafé på Øya"), "⇑kaf´e på ⇑øya");
+        assert_eq!(refine("german", "„Straße“ É"), "⇑'straße⇑' ´⇑e");
+        assert_eq!(refine("french", "Œuvre fête"), "⇑oeuvre f^ete");
+        assert_eq!(refine("spanish", "¿Qué?"), "¿⇑qu´e⇑/");
+        assert_eq!(refine("dutch", "ĳs één"), "ijs ´e´en");
+    }
+}
@@ -0,0 +1,456 @@
+//! Project files, which describe everything that's needed to set up an [`Optimizer`] in one
+//! place.
+//!
+//! ```toml
+//! corpus = "data/english.json"
+//! weights = "weights.toml"
+//! keyboard = "boards/corne.json"
+//! refiner = "english"
+//! layout = "layouts/start.dof"
+//! pins = "aeiou"
+//! ```
+//!
+//! Paths are relative to the project file. Everything but the corpus and layout can also be
+//! written inline, like `keyboard = { shape = [10, 10, 10] }` or a `[weights]` table.
+
+use std::path::{Path, PathBuf};
+
+use libdof::{dofinitions::Finger, Dof};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    data::{Data, DataError},
+    keyboard::Keyboard,
+    kle::{self, FingerAssignment, KleError},
+    layout::{Layout, LayoutError},
+    optimizer::Optimizer,
+    presets::{refiner_preset, REFINER_PRESETS},
+    refiner_config::{RefinerConfig, RefinerConfigError},
+    trigram_types::TrigramTypes,
+    weights::{Weights, WeightsError, WeightsWarning},
+};
+
+/// Name of the project file that's looked for in the working directory.
+pub const PROJECT_FILE: &str = "oxeygen.toml";
+
+#[derive(Debug, Error)]
+pub enum ProjectError {
+    #[error("Couldn't read '{path}': {source}")]
+    ReadError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("'{path}' is not a valid project file: {source}")]
+    TomlError {
+        path: String,
+        source: Box<toml::de::Error>,
+    },
+
+    #[error("'{0}' is not a valid .dof file: {1}")]
+    DofError(String, serde_json::Error),
+
+    #[error("{0}")]
+    WeightsError(#[from] WeightsError),
+
+    #[error("Unknown refiner '{0}', expected a config file or one of {1}")]
+    UnknownRefiner(String, String),
+
+    #[error("'{0}' is not a finger")]
+    UnknownFinger(String),
+
+    #[error("No {0} is set, add it to {PROJECT_FILE} or pass it as a flag")]
+    Missing(&'static str),
+
+    #[error("'{0}' is not on the layout, so it can't be pinned")]
+    UnknownPin(char),
+
+    #[error("{0}")]
+    DataError(#[from] DataError),
+
+    #[error("{0}")]
+    RefinerConfigError(#[from] RefinerConfigError),
+
+    #[error("Invalid keyboard: {0}")]
+    KleError(#[from] KleError),
+
+    #[error("Invalid layout: {0}")]
+    LayoutError(#[from] LayoutError),
+}
+
+/// Something that's either stored in a file of its own or written inline.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Source<T> {
+    Path(PathBuf),
+    Inline(T),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyboardSource {
+    /// A .dof file, whose fingering is used, or a keyboard-layout-editor .json file.
+    Path(PathBuf),
+    /// Rows of the given lengths with a traditional fingering, see [`Keyboard::from_shape`].
+    Shape { shape: Vec<usize> },
+    /// Rows of fingers separated by whitespace, like `"LP LR LM LI LI  RI RI RM RR RP"`.
+    Fingering { fingering: Vec<String> },
+}
+
+/// The contents of a project file. Every field is optional, so the ones that are missing can be
+/// passed in some other way.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Project {
+    /// A corpus .json file. Unlike the other fields, it can't be written inline.
+    pub corpus: Option<PathBuf>,
+    pub weights: Option<Source<Weights>>,
+    pub keyboard: Option<KeyboardSource>,
+    /// A refiner preset, or a refiner config as .toml or .json.
+    pub refiner: Option<Source<RefinerConfig>>,
+    /// A layout to start from, as a .dof file or in the text format of [`Layout::parse`].
+    pub layout: Option<PathBuf>,
+    /// Characters to place when there's no layout to start from.
+    pub chars: Option<String>,
+    /// Characters that keep their position on the layout to start from.
+    pub pins: Option<String>,
+}
+
+impl Project {
+    /// Reads a project file. Relative paths in it are made relative to the folder of the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ProjectError> {
+        let path = path.as_ref();
+        let content = read(path)?;
+
+        let project = toml::from_str::<Self>(&content).map_err(|e| ProjectError::TomlError {
+            path: path.display().to_string(),
+            source: Box::new(e),
+        })?;
+
+        Ok(project.relative_to(path.parent().unwrap_or(Path::new(""))))
+    }
+
+    /// Loads the project file in `folder`, if there is one.
+    pub fn discover<P: AsRef<Path>>(folder: P) -> Result<Option<Self>, ProjectError> {
+        let path = folder.as_ref().join(PROJECT_FILE);
+
+        match path.is_file() {
+            true => Self::load(path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn relative_to(mut self, folder: &Path) -> Self {
+        let join = |path: &mut PathBuf| *path = folder.join(&*path);
+
+        if let Some(path) = &mut self.corpus {
+            join(path);
+        }
+        if let Some(Source::Path(path)) = &mut self.weights {
+            join(path);
+        }
+        if let Some(KeyboardSource::Path(path)) = &mut self.keyboard {
+            join(path);
+        }
+        if let Some(Source::Path(path)) = &mut self.refiner {
+            if refiner_preset(&path.to_string_lossy()).is_none() {
+                join(path);
+            }
+        }
+        if let Some(path) = &mut self.layout {
+            join(path);
+        }
+
+        self
+    }
+
+    pub fn corpus(&self) -> Result<Data, ProjectError> {
+        let path = self
+            .corpus
+            .as_ref()
+            .ok_or(ProjectError::Missing("corpus"))?;
+        Ok(Data::load(path)?)
+    }
+
+    pub fn weights(&self) -> Result<Weights, ProjectError> {
+        match &self.weights {
+            Some(Source::Path(path)) => Ok(Weights::load(path)?),
+            Some(Source::Inline(weights)) => Ok(weights.clone()),
+            None => Err(ProjectError::Missing("weights")),
+        }
+    }
+
+    /// The refiner config, which is the english preset if none is set.
+    pub fn refiner(&self) -> Result<RefinerConfig, ProjectError> {
+        match &self.refiner {
+            Some(Source::Path(path)) => {
+                let name = path.to_string_lossy();
+                let is_config = path.extension().is_some_and(|e| e == "toml" || e == "json");
+
+                if let Some(config) = refiner_preset(&name) {
+                    Ok(config)
+                } else if is_config || path.exists() {
+                    Ok(RefinerConfig::load(path)?)
+                } else {
+                    Err(ProjectError::UnknownRefiner(
+                        name.into(),
+                        REFINER_PRESETS.join(", "),
+                    ))
+                }
+            }
+            Some(Source::Inline(config)) => Ok(config.clone()),
+            None => Ok(refiner_preset("english").expect("the english preset exists")),
+        }
+    }
+
+    /// The keyboard that's set, or else the keyboard of the layout to start from. Returns `None`
+    /// if neither is set.
+    pub fn keyboard(&self) -> Result<Option<Keyboard>, ProjectError> {
+        let keyboard = match &self.keyboard {
+            Some(KeyboardSource::Path(path)) => match is_dof(path) {
+                true => Keyboard::from_dof(&LayoutFile::read_dof(path)?),
+                false => kle::load(path, FingerAssignment::Auto)?,
+            },
+            Some(KeyboardSource::Shape { shape }) => Keyboard::from_shape(shape),
+            Some(KeyboardSource::Fingering { fingering }) => {
+                let rows = fingering
+                    .iter()
+                    .map(|row| {
+                        row.split_whitespace()
+                            .map(|f| {
+                                f.parse::<Finger>()
+                                    .map_err(|_| ProjectError::UnknownFinger(f.into()))
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let shape = rows.iter().map(Vec::len).collect::<Vec<_>>();
+                let fingers = rows.concat();
+
+                Keyboard::new(&fingers)
+                    .with_shape(&shape)
+                    .expect("the shape adds up to the amount of fingers")
+            }
+            None => match &self.layout {
+                Some(path) => LayoutFile::load(path)?.keyboard(),
+                None => return Ok(None),
+            },
+        };
+
+        Ok(Some(keyboard))
+    }
+
+    /// The default trigram types for the keyboard of the project, or for a 3x10 keyboard if it
+    /// doesn't have one.
+    pub fn trigram_types(&self) -> Result<TrigramTypes<'static>, ProjectError> {
+        let keyboard = self
+            .keyboard()?
+            .unwrap_or_else(|| Keyboard::from_shape(&[10, 10, 10]));
+
+        Ok(TrigramTypes::with_defaults(keyboard))
+    }
+
+    /// Creates an optimizer with the corpus and weights of the project, after checking that the
+    /// weights match `types`. Also returns what was suspicious about the weights.
+    pub fn optimizer<'a>(
+        &self,
+        types: &'a TrigramTypes<'a>,
+    ) -> Result<(Optimizer<'a>, Vec<WeightsWarning>), ProjectError> {
+        let weights = self.weights()?;
+        let warnings = weights.validate(types)?;
+
+        Ok((Optimizer::new(types, &self.corpus()?, weights), warnings))
+    }
+
+    /// The layout to start optimizing from: the layout of the project, or otherwise its characters
+    /// placed in order. Without either, the most common characters of the corpus are used. Pins
+    /// are applied to the result.
+    pub fn template(&self, optimizer: &Optimizer) -> Result<Layout, ProjectError> {
+        let mut template = match (&self.layout, &self.chars) {
+            (Some(path), _) => LayoutFile::load(path)?.layout(optimizer)?,
+            (None, chars) => {
+                let chars = match chars {
+                    Some(chars) => chars.chars().collect(),
+                    None => optimizer.most_common_chars(optimizer.len()),
+                };
+
+                optimizer.layout(&chars, optimizer.keyboard().fingering())?
+            }
+        };
+
+        for c in self.pins.iter().flat_map(|pins| pins.chars()) {
+            let pos = optimizer
+                .position(&template, c)
+                .ok_or(ProjectError::UnknownPin(c))?;
+
+            template.pin(pos);
+        }
+
+        Ok(template)
+    }
+}
+
+/// A layout as it was read from a file, before it's known which characters the corpus has.
+pub enum LayoutFile {
+    Text { name: String, layout: String },
+    Dof(Dof),
+}
+
+impl LayoutFile {
+    /// Reads a .dof file, or a layout in the text format of [`Layout::parse`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ProjectError> {
+        let path = path.as_ref();
+
+        match is_dof(path) {
+            true => Ok(Self::Dof(Self::read_dof(path)?)),
+            false => Ok(Self::Text {
+                name: path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string()),
+                layout: read(path)?,
+            }),
+        }
+    }
+
+    fn read_dof(path: &Path) -> Result<Dof, ProjectError> {
+        serde_json::from_str(&read(path)?)
+            .map_err(|e| ProjectError::DofError(path.display().to_string(), e))
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Text { name, .. } => name,
+            Self::Dof(dof) => dof.name(),
+        }
+    }
+
+    /// The keyboard the layout was made for. A .dof file has its own fingering, while a text
+    /// layout gets a traditional fingering that follows its rows.
+    pub fn keyboard(&self) -> Keyboard {
+        match self {
+            Self::Text { layout, .. } => {
+                let shape = layout
+                    .lines()
+                    .map(|l| l.split_whitespace().count())
+                    .filter(|&len| len > 0)
+                    .collect::<Vec<_>>();
+
+                Keyboard::from_shape(&shape)
+            }
+            Self::Dof(dof) => Keyboard::from_dof(dof),
+        }
+    }
+
+    /// Reads the layout for the keyboard and corpus of `optimizer`.
+    pub fn layout(&self, optimizer: &Optimizer) -> Result<Layout, LayoutError> {
+        match self {
+            Self::Text { layout, .. } => optimizer.parse_layout(layout),
+            Self::Dof(dof) => optimizer.layout_from_dof(dof),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, ProjectError> {
+    std::fs::read_to_string(path).map_err(|source| ProjectError::ReadError {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+fn is_dof(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "dof")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load() {
+        let folder = std::env::temp_dir().join("oxeygen-project");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(
+            folder.join("start.txt"),
+            "q w e r t y u i o p\na s d f g h j k l ;\nz x c v b n m , . '",
+        )
+        .unwrap();
+        std::fs::write(
+            folder.join(PROJECT_FILE),
+            r#"
+                layout = "start.txt"
+                pins = "ae"
+                refiner = "norwegian"
+
+                [weights]
+                Sfb = -3
+
+                [weights.fingers]
+                LP = 1
+            "#,
+        )
+        .unwrap();
+
+        let mut project = Project::discover(&folder).unwrap().unwrap();
+        assert_eq!(project.layout, Some(folder.join("start.txt")));
+        assert!(
+            matches!(project.refiner, Some(Source::Path(ref p)) if p == Path::new("norwegian"))
+        );
+        assert_eq!(project.weights().unwrap().get("Sfb"), -3.0);
+
+        assert!(matches!(
+            project.corpus(),
+            Err(ProjectError::Missing("corpus"))
+        ));
+
+        project.corpus = Some("../data/shai.json".into());
+
+        let types = project.trigram_types().unwrap();
+        assert_eq!(types.keyboard().shape(), [10, 10, 10]);
+
+        let (optimizer, _) = project.optimizer(&types).unwrap();
+        let template = project.template(&optimizer).unwrap();
+        assert!(template.is_pinned(10) && template.is_pinned(2));
+        assert!(!template.is_pinned(0));
+
+        project.pins = Some("€".into());
+        assert!(matches!(
+            project.template(&optimizer),
+            Err(ProjectError::UnknownPin('€'))
+        ));
+
+        project.layout = None;
+        project.pins = None;
+        project.keyboard = Some(KeyboardSource::Fingering {
+            fingering: vec!["LP LR LM LI  RI RM RR RP".into(), "LT RT".into()],
+        });
+        assert_eq!(project.keyboard().unwrap().unwrap().shape(), [8, 2]);
+
+        let types = project.trigram_types().unwrap();
+        let (optimizer, _) = project.optimizer(&types).unwrap();
+        let template = project.template(&optimizer).unwrap();
+        assert_eq!(
+            optimizer.position(&template, optimizer.most_common_chars(1)[0]),
+            Some(0)
+        );
+
+        project.chars = Some("aabc".into());
+        assert!(matches!(
+            project.template(&optimizer),
+            Err(ProjectError::LayoutError(LayoutError::DuplicateChar('a')))
+        ));
+        project.chars = Some("ab€".into());
+        assert!(matches!(
+            project.template(&optimizer),
+            Err(ProjectError::LayoutError(LayoutError::UnknownChar('€')))
+        ));
+
+        assert!(Project::discover(std::env::temp_dir().join("oxeygen-none"))
+            .unwrap()
+            .is_none());
+
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
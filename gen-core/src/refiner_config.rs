@@ -0,0 +1,243 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use thiserror::Error;
+
+use crate::{corpus_refiner::CorpusRefiner, SHIFT_CHAR};
+
+/// A description of a [`CorpusRefiner`] that can be read from and written to TOML or JSON. Rules
+/// are applied in the order of the fields, so a conversion overrides an include of the same
+/// character, for example.
+///
+/// ```toml
+/// include = "abcdefghijklmnopqrstuvwxyz"
+/// uppercase = true
+/// space = true
+/// qwerty_punct_casings = true
+///
+/// [convert]
+/// "é" = "e"
+///
+/// [[sequences]]
+/// from = "qu"
+/// to = "*"
+/// uppercase = true
+/// ```
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RefinerConfig {
+    /// Characters that are kept as they are.
+    pub include: String,
+    /// Also include the uppercase versions of `include`, typed as shift and the lowercase
+    /// character.
+    pub uppercase: bool,
+    pub space: bool,
+    /// Spaces, newlines and tabs.
+    pub ascii_whitespace: bool,
+    /// Punctuation and its shifted version on a qwerty keyboard, like `;` and `:`.
+    pub qwerty_punct_casings: bool,
+    /// Turns curly quotes, different dashes and similar into their plain versions.
+    pub normalize_punct: bool,
+    /// Lowercase characters mapped to their uppercase version, which is typed with shift.
+    #[serde_as(as = "IndexMap<DisplayFromStr, _>")]
+    pub casing: IndexMap<char, char>,
+    #[serde_as(as = "IndexMap<DisplayFromStr, _>")]
+    pub convert: IndexMap<char, char>,
+    pub dead_keys: Vec<DeadKeyConfig>,
+    pub sequences: Vec<SequenceConfig>,
+    pub repeat_key: bool,
+    /// Filters out the shift key instead of counting it as a character.
+    pub exclude_shift: bool,
+}
+
+/// Characters typed by pressing `key` followed by another character.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeadKeyConfig {
+    pub key: char,
+    #[serde_as(as = "IndexMap<DisplayFromStr, _>")]
+    pub chars: IndexMap<char, char>,
+    /// Also type the uppercase versions of `chars`, as `key` followed by shift and the character.
+    #[serde(default)]
+    pub uppercase: bool,
+}
+
+/// See [`CorpusRefinerBuilder::multiple_char_rule`](crate::corpus_refiner::CorpusRefinerBuilder::multiple_char_rule).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SequenceConfig {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub uppercase: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum RefinerConfigError {
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("{0}")]
+    TomlError(#[from] toml::de::Error),
+
+    #[error("{0}")]
+    TomlSerializeError(#[from] toml::ser::Error),
+
+    #[error("{0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Refiner configs should be either .toml or .json files, found '{0}'")]
+    UnknownFormat(String),
+}
+
+impl RefinerConfig {
+    pub fn build(&self) -> CorpusRefiner {
+        let mut builder = CorpusRefiner::builder();
+
+        builder.include(self.include.chars(), self.uppercase);
+
+        if self.space {
+            builder.include_space();
+        }
+        if self.ascii_whitespace {
+            builder.include_ascii_whitespace();
+        }
+        if self.qwerty_punct_casings {
+            builder.include_qwerty_punct_casings();
+        }
+        if self.normalize_punct {
+            builder.normalize_miscellaneous_punct();
+        }
+
+        builder
+            .with_uppercase(self.casing.iter().map(|(&l, &u)| (l, u)), true)
+            .convert(self.convert.iter().map(|(&f, &t)| (f, t)));
+
+        for dead_key in self.dead_keys.iter() {
+            builder.dead_key(dead_key.chars.iter().map(|(&f, &t)| (f, t)), dead_key.key);
+
+            if dead_key.uppercase {
+                for (from, to) in dead_key.chars.iter() {
+                    let upper = from.to_uppercase().collect::<String>();
+                    let to = format!("{}{SHIFT_CHAR}{to}", dead_key.key);
+
+                    if upper.chars().count() == 1 && upper != from.to_string() {
+                        builder.multiple_char_rule(&upper, &to, false);
+                    }
+                }
+            }
+        }
+
+        for sequence in self.sequences.iter() {
+            builder.multiple_char_rule(&sequence.from, &sequence.to, sequence.uppercase);
+        }
+
+        builder.repeat_key(self.repeat_key);
+
+        if self.exclude_shift {
+            builder.exclude_shift_char();
+        }
+
+        let mut refiner = builder.build();
+        refiner.config = Some(self.clone());
+
+        refiner
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, RefinerConfigError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, RefinerConfigError> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, RefinerConfigError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, RefinerConfigError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Loads a config from a `.toml` or `.json` file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, RefinerConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(RefinerConfigError::UnknownFormat(
+                path.display().to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus_refiner::RefineCorpus;
+
+    const CONFIG: &str = r#"
+        include = "abcdefghijklmnopqrstuvwxyz"
+        uppercase = true
+        space = true
+        repeat_key = true
+
+        [casing]
+        "1" = "!"
+
+        [convert]
+        "é" = "e"
+
+        [[dead_keys]]
+        key = "*"
+        chars = { "ñ" = "n" }
+
+        [[sequences]]
+        from = "qu"
+        to = "%"
+        uppercase = true
+    "#;
+
+    #[test]
+    fn matches_builder() {
+        let config = RefinerConfig::from_toml(CONFIG).unwrap();
+
+        let built = CorpusRefiner::builder()
+            .include("abcdefghijklmnopqrstuvwxyz".chars(), true)
+            .include_space()
+            .with_uppercase([('1', '!')], true)
+            .convert([('é', 'e')])
+            .dead_key([('ñ', 'n')].into_iter(), '*')
+            .multiple_char_rule("qu", "%", true)
+            .repeat_key(true)
+            .build();
+
+        let text = "Quite the café! Señor, 1 book";
+        let refine = |r: &CorpusRefiner| text.chars().refine(r).flatten().collect::<String>();
+
+        assert_eq!(refine(&config.build()), refine(&built));
+        assert_eq!(config.build().config(), Some(&config));
+        assert_eq!(built.config(), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = RefinerConfig::from_toml(CONFIG).unwrap();
+
+        let toml = config.to_toml().unwrap();
+        assert_eq!(RefinerConfig::from_toml(&toml).unwrap(), config);
+
+        let json = config.to_json().unwrap();
+        assert_eq!(RefinerConfig::from_json(&json).unwrap(), config);
+
+        assert!(RefinerConfig::from_toml("inclde = \"abc\"").is_err());
+        assert!(RefinerConfig::from_toml("[convert]\n\"ab\" = \"c\"").is_err());
+    }
+}
@@ -52,11 +52,21 @@ pub mod default {
 
     pub struct Unspecified;
 
+    /// Hand and finger order of every position in the trigram, according to the hand model of
+    /// the keyboard.
+    fn finger_infos(keyboard: &Keyboard, [p1, p2, p3]: [Pos; 3]) -> Option<[FingerInfo; 3]> {
+        Some([
+            keyboard.get_finger_info(p1)?,
+            keyboard.get_finger_info(p2)?,
+            keyboard.get_finger_info(p3)?,
+        ])
+    }
+
     impl TrigramType for Sfb {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
             let fingerings = keyboard.get_fingers(positions);
             let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
+                [Some(f1), Some(f2), Some(f3)] => (f1, f2, f3),
                 _ => return false,
             };
 
@@ -82,7 +92,7 @@ pub mod default {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
             let fingerings = keyboard.get_fingers(positions);
             let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
+                [Some(f1), Some(f2), Some(f3)] => (f1, f2, f3),
                 _ => return false,
             };
 
@@ -96,25 +106,12 @@ pub mod default {
 
     impl TrigramType for Inroll {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
-            let fingerings = keyboard.get_fingers(positions);
-            let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
-                _ => return false,
+            let [i1, i2, i3] = match finger_infos(keyboard, positions) {
+                Some(infos) => infos,
+                None => return false,
             };
 
-            let [lh1, lh2, lh3] = [f1 < 5, f2 < 5, f3 < 5];
-
-            // (lh1 && lh2 && !lh3) && f1 < f2
-            //     || (!lh1 && lh2 && lh3) && f2 < f3
-            //     || (!lh1 && !lh2 && lh3) && f1 > f2
-            //     || (lh1 && !lh2 && !lh3) && f2 > f3
-
-            (f2 > f3 || lh3 || lh2)
-                && (f1 > f2 || lh2 || lh1)
-                && (f2 < f3 || !lh2 || lh1)
-                && (f1 < f2 || lh3 || !lh2)
-                && (lh3 || lh1)
-                && (!lh3 || !lh1)
+            (i2.hand != i3.hand && i1.is_inward(&i2)) || (i1.hand != i2.hand && i2.is_inward(&i3))
         }
 
         fn display(&self) -> &str {
@@ -124,25 +121,12 @@ pub mod default {
 
     impl TrigramType for Outroll {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
-            let fingerings = keyboard.get_fingers(positions);
-            let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
-                _ => return false,
+            let [i1, i2, i3] = match finger_infos(keyboard, positions) {
+                Some(infos) => infos,
+                None => return false,
             };
 
-            let [lh1, lh2, lh3] = [f1 < 5, f2 < 5, f3 < 5];
-
-            // (lh1 && lh2 && !lh3) && f1 > f2
-            //     || (!lh1 && lh2 && lh3) && f2 > f3
-            //     || (!lh1 && !lh2 && lh3) && f1 < f2
-            //     || (lh1 && !lh2 && !lh3) && f2 < f3
-
-            (f2 < f3 || lh3 || lh2)
-                && (f1 < f2 || lh2 || lh1)
-                && (f2 > f3 || !lh2 || lh1)
-                && (f1 > f2 || lh3 || !lh2)
-                && (lh3 || lh1)
-                && (!lh3 || !lh1)
+            (i2.hand != i3.hand && i1.is_outward(&i2)) || (i1.hand != i2.hand && i2.is_outward(&i3))
         }
 
         fn display(&self) -> &str {
@@ -152,15 +136,12 @@ pub mod default {
 
     impl TrigramType for Alternation {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
-            let fingerings = keyboard.get_fingers(positions);
-            let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
-                _ => return false,
+            let [i1, i2, i3] = match finger_infos(keyboard, positions) {
+                Some(infos) => infos,
+                None => return false,
             };
 
-            let [lh1, lh2, lh3] = [f1 < 5, f2 < 5, f3 < 5];
-
-            lh1 && !lh2 && lh3 || !lh1 && lh2 && !lh3
+            i1.hand != i2.hand && i2.hand != i3.hand
         }
 
         fn display(&self) -> &str {
@@ -170,16 +151,12 @@ pub mod default {
 
     impl TrigramType for OnehandIn {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
-            let fingerings = keyboard.get_fingers(positions);
-            let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
-                _ => return false,
+            let [i1, i2, i3] = match finger_infos(keyboard, positions) {
+                Some(infos) => infos,
+                None => return false,
             };
 
-            let [lh1, lh2, lh3] = [f1 < 5, f2 < 5, f3 < 5];
-
-            (lh1 && lh2 && lh3) && (f1 < f2 && f2 < f3)
-                || !(lh1 || lh2 || lh3) && (f1 > f2 && f2 > f3)
+            i1.is_inward(&i2) && i2.is_inward(&i3)
         }
 
         fn display(&self) -> &str {
@@ -189,16 +166,12 @@ pub mod default {
 
     impl TrigramType for OnehandOut {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
-            let fingerings = keyboard.get_fingers(positions);
-            let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
-                _ => return false,
+            let [i1, i2, i3] = match finger_infos(keyboard, positions) {
+                Some(infos) => infos,
+                None => return false,
             };
 
-            let [lh1, lh2, lh3] = [f1 < 5, f2 < 5, f3 < 5];
-
-            (lh1 && lh2 && lh3) && (f1 > f2 && f2 > f3)
-                || !(lh1 || lh2 || lh3) && (f1 < f2 && f2 < f3)
+            i1.is_outward(&i2) && i2.is_outward(&i3)
         }
 
         fn display(&self) -> &str {
@@ -208,15 +181,12 @@ pub mod default {
 
     impl TrigramType for Redirect {
         fn is_type(&self, keyboard: &Keyboard, positions: [Pos; 3]) -> bool {
-            let fingerings = keyboard.get_fingers(positions);
-            let (f1, f2, f3) = match fingerings {
-                [Some(f1), Some(f2), Some(f3)] => (f1 as u8, f2 as u8, f3 as u8),
-                _ => return false,
+            let [i1, i2, i3] = match finger_infos(keyboard, positions) {
+                Some(infos) => infos,
+                None => return false,
             };
 
-            let [lh1, lh2, lh3] = [f1 < 5, f2 < 5, f3 < 5];
-
-            (lh1 == lh2 && lh2 == lh3) && ((f1 < f2 && f2 > f3) || (f1 > f2 && f2 < f3))
+            (i1.is_inward(&i2) && i2.is_outward(&i3)) || (i1.is_outward(&i2) && i2.is_inward(&i3))
         }
 
         fn display(&self) -> &str {
@@ -360,6 +330,15 @@ impl<'a> TrigramTypes<'a> {
         }
     }
 
+    /// Uses the same trigram types to classify a different keyboard.
+    pub fn with_keyboard(&self, keyboard: Keyboard) -> Self {
+        Self {
+            keyboard,
+            types: self.types.clone(),
+            default: self.default,
+        }
+    }
+
     pub fn add_type(&'a mut self, t: &'a dyn TrigramType) -> Option<()> {
         self.types.push(t);
 
@@ -394,4 +373,83 @@ mod tests {
 
         assert!(default.add_type(&Sfb).is_none());
     }
+
+    #[test]
+    fn thumbs() {
+        let keyboard = Keyboard::new(&[LP, LR, LM, LI, LT, RT, RI, RM, RR, RP]);
+        let types = TrigramTypes::with_defaults(keyboard);
+
+        assert_eq!(types.get_type([0, 1, 9]).display(), "Inroll");
+        assert_eq!(types.get_type([3, 4, 9]).display(), "Unspecified");
+        assert_eq!(types.get_type([4, 5, 4]).display(), "Alternation");
+        assert_eq!(types.get_type([1, 4, 2]).display(), "Unspecified");
+        assert_eq!(types.get_type([4, 4, 4]).display(), "Sfr");
+    }
+
+    #[test]
+    fn one_handed() {
+        let hands = HandModel::from_order(&[LP, LR, LM, LI, LT], &[]).unwrap();
+        let keyboard = Keyboard::with_hands(&[LP, LR, LM, LI, LT], hands).unwrap();
+        let types = TrigramTypes::with_defaults(keyboard);
+
+        assert_eq!(types.get_type([0, 1, 4]).display(), "Onehand In");
+        assert_eq!(types.get_type([4, 2, 0]).display(), "Onehand Out");
+        assert_eq!(types.get_type([0, 4, 1]).display(), "Redirect");
+
+        for i in 0..5 {
+            for j in 0..5 {
+                for k in 0..5 {
+                    let t = types.get_type([i, j, k]).display();
+                    assert!(!["Alternation", "Inroll", "Outroll"].contains(&t));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn custom_order() {
+        let hands = HandModel::from_order(&[LI, LM, LR, LP], &[RP, RR, RM, RI]).unwrap();
+        let keyboard = Keyboard::with_hands(&[LP, LR, LM, LI, RI, RM, RR, RP], hands).unwrap();
+        let types = TrigramTypes::with_defaults(keyboard);
+
+        assert_eq!(types.get_type([0, 1, 4]).display(), "Outroll");
+        assert_eq!(types.get_type([3, 2, 1]).display(), "Onehand In");
+        assert_eq!(types.get_type([7, 6, 5]).display(), "Onehand In");
+    }
+
+    #[test]
+    fn custom_order_rows() {
+        #[rustfmt::skip]
+        let fingering = [
+            LI, LM, LR, LP,  RP, RR, RM, RI,
+            LI, LM, LR, LP,  RP, RR, RM, RI,
+            LI, RI,
+        ];
+
+        let hands = HandModel::from_order(&[LI, LM, LR, LP], &[RI, RM, RR, RP]).unwrap();
+        let keyboard = Keyboard::with_hands(&fingering, hands).unwrap();
+
+        assert_eq!(keyboard.shape(), [8, 8, 2]);
+
+        let default = Keyboard::from_shape(&[10, 10, 10, 2]);
+        assert_eq!(Keyboard::new(default.fingering()).shape(), [10, 10, 10, 2]);
+    }
+
+    #[test]
+    fn hand_model_errors() {
+        let hands = HandModel::from_order(&[LP, LR, LM, LI], &[RI, RM, RR, RP]).unwrap();
+
+        assert_eq!(
+            Keyboard::with_hands(&[LP, LT], hands.clone()).unwrap_err(),
+            HandModelError::UnassignedFinger(LT)
+        );
+        assert_eq!(
+            hands.clone().assign(LP, Hand::Right, None).unwrap_err(),
+            HandModelError::DuplicateFinger(LP)
+        );
+        assert_eq!(
+            hands.assign(LT, Hand::Left, Some(0)).unwrap_err(),
+            HandModelError::DuplicateOrder(LP, LT)
+        );
+    }
 }
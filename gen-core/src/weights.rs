@@ -1,10 +1,65 @@
-use std::{collections::HashMap, path::Path};
+use std::{collections::HashMap, fmt, path::Path};
 
 use libdof::dofinitions::Finger;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use thiserror::Error;
 
 use serde_with::{serde_as, DisplayFromStr};
 
+use crate::trigram_types::TrigramTypes;
+
+const FINGERS: [Finger; 10] = {
+    use Finger::*;
+    [LP, LR, LM, LI, LT, RT, RI, RM, RR, RP]
+};
+
+#[derive(Debug, Error)]
+pub enum WeightsError {
+    #[error("Couldn't read '{path}': {source}")]
+    ReadError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("{path}:{line}:{column}: {message}")]
+    ParseError {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("Unknown trigram type '{name}' in weights, expected one of {expected}")]
+    UnknownType { name: String, expected: String },
+}
+
+/// Something about weights that's allowed, but likely not what was meant.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum WeightsWarning {
+    #[error("No weight for {}, so these trigram types don't affect the score", .0.join(", "))]
+    MissingTypes(Vec<String>),
+
+    #[error("No trigram on the keyboard is {}, so their weights are unused", .0.join(" or "))]
+    UnusedTypes(Vec<String>),
+
+    #[error("No weight for {}, which the keyboard uses", list(.0))]
+    MissingFingers(Vec<Finger>),
+
+    #[error("The keyboard has no keys for {}, so their weights are unused", list(.0))]
+    UnusedFingers(Vec<Finger>),
+}
+
+fn list(fingers: &[Finger]) -> String {
+    fingers
+        .iter()
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[serde_as]
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FingerWeights(#[serde_as(as = "HashMap<DisplayFromStr, _>")] HashMap<Finger, f32>);
@@ -15,17 +70,147 @@ impl FingerWeights {
     }
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct Weights {
     #[serde(flatten)]
     weights: HashMap<String, f32>,
     fingers: FingerWeights,
 }
 
+// Deserialized by hand rather than with `#[serde(flatten)]`, which would lose the position of
+// errors in the file.
+impl<'de> Deserialize<'de> for Weights {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct WeightsVisitor;
+
+        impl<'de> Visitor<'de> for WeightsVisitor {
+            type Value = Weights;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("weights of trigram types and a table of finger weights")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Weights, A::Error> {
+                let mut weights = Weights::default();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "fingers" => weights.fingers = map.next_value()?,
+                        _ => {
+                            let weight = map.next_value()?;
+                            weights.weights.insert(key, weight);
+                        }
+                    }
+                }
+
+                Ok(weights)
+            }
+        }
+
+        deserializer.deserialize_map(WeightsVisitor)
+    }
+}
+
 impl Weights {
-    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
-        let content = std::fs::read_to_string(path).ok()?;
-        toml::from_str(&content).ok()
+    /// Reads weights from a .toml file. Parse errors point at the line and column they're on.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WeightsError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|source| WeightsError::ReadError {
+            path: path.display().to_string(),
+            source,
+        })?;
+
+        toml::from_str(&content).map_err(|e| {
+            let offset = e.span().map(|span| span.start).unwrap_or(0);
+            let (line, column) = line_column(&content, offset);
+
+            WeightsError::ParseError {
+                path: path.display().to_string(),
+                line,
+                column,
+                message: e.message().trim_end().to_string(),
+            }
+        })
+    }
+
+    /// Checks that every weight belongs to one of `types`, since the weight of a misspelled name
+    /// would silently be ignored otherwise. Finger names are already checked when loading.
+    ///
+    /// Weights that nothing on the keyboard of `types` uses, and types or fingers without a weight,
+    /// are returned as warnings. The catch-all type doesn't need a weight.
+    pub fn validate(&self, types: &TrigramTypes) -> Result<Vec<WeightsWarning>, WeightsError> {
+        let names = types
+            .types()
+            .iter()
+            .chain([&types.default()])
+            .map(|t| t.display())
+            .collect::<Vec<_>>();
+
+        let mut unknown = self
+            .weights
+            .keys()
+            .filter(|name| !names.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        unknown.sort();
+
+        if let Some(name) = unknown.first() {
+            return Err(WeightsError::UnknownType {
+                name: name.to_string(),
+                expected: names.join(", "),
+            });
+        }
+
+        Ok(self.warnings(types))
+    }
+
+    fn warnings(&self, types: &TrigramTypes) -> Vec<WeightsWarning> {
+        let keyboard = types.keyboard();
+        let len = keyboard.len();
+
+        let mut used = Vec::new();
+        for i in 0..len {
+            for j in 0..len {
+                for k in 0..len {
+                    let name = types.get_type([i, j, k]).display();
+                    if !used.contains(&name) {
+                        used.push(name);
+                    }
+                }
+            }
+        }
+
+        let named = types.types().iter().map(|t| t.display());
+        let missing_types = named
+            .clone()
+            .filter(|name| !self.weights.contains_key(*name))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let unused_types = named
+            .chain([types.default().display()])
+            .filter(|name| self.weights.contains_key(*name) && !used.contains(name))
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let on_keyboard = |f: &Finger| keyboard.fingering().contains(f);
+        let missing_fingers = FINGERS
+            .into_iter()
+            .filter(|f| on_keyboard(f) && !self.fingers.0.contains_key(f))
+            .collect::<Vec<_>>();
+        let unused_fingers = FINGERS
+            .into_iter()
+            .filter(|f| !on_keyboard(f) && self.fingers.0.contains_key(f))
+            .collect::<Vec<_>>();
+
+        [
+            (!missing_types.is_empty()).then_some(WeightsWarning::MissingTypes(missing_types)),
+            (!unused_types.is_empty()).then_some(WeightsWarning::UnusedTypes(unused_types)),
+            (!missing_fingers.is_empty())
+                .then_some(WeightsWarning::MissingFingers(missing_fingers)),
+            (!unused_fingers.is_empty()).then_some(WeightsWarning::UnusedFingers(unused_fingers)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
     }
 
     pub fn get(&self, name: &str) -> f32 {
@@ -37,9 +222,116 @@ impl Weights {
     }
 }
 
-#[test]
-fn load() {
-    let weights = Weights::load("./weights.toml");
+/// 1-based line and column of a byte offset.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+
+    (line, column)
+}
 
-    println!("{weights:#?}");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::Keyboard;
+
+    #[test]
+    fn load() {
+        let weights = Weights::load("./weights.toml").unwrap();
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+
+        assert_eq!(weights.get("Sfb"), -3.0);
+        assert_eq!(weights.get_finger_trigram([Finger::LP; 3]), 3.0);
+        assert!(weights.validate(&types).is_ok());
+    }
+
+    #[test]
+    fn warnings() {
+        use Finger::*;
+
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+        let weights = |s: &str| {
+            toml::from_str::<Weights>(s)
+                .unwrap()
+                .validate(&types)
+                .unwrap()
+        };
+
+        let all = "Sfb = -3\nSfr = -1\nSft = -4\nInroll = 2\nOutroll = 2\nAlternation = 1.5
+            Redirect = -0.8\n\"Onehand In\" = 0.3\n\"Onehand Out\" = 0.3
+            [fingers]\nLP = 1\nLR = 1\nLM = 1\nLI = 1\nRI = 1\nRM = 1\nRR = 1\nRP = 1";
+        assert_eq!(weights(all), []);
+
+        // Types and fingers without a weight.
+        assert_eq!(
+            weights("Sfb = -3\n[fingers]\nLP = 1"),
+            [
+                WeightsWarning::MissingTypes(
+                    [
+                        "Sfr",
+                        "Sft",
+                        "Inroll",
+                        "Outroll",
+                        "Alternation",
+                        "Onehand In",
+                        "Onehand Out",
+                        "Redirect"
+                    ]
+                    .map(String::from)
+                    .to_vec()
+                ),
+                WeightsWarning::MissingFingers(vec![LR, LM, LI, RI, RM, RR, RP]),
+            ]
+        );
+
+        // Weights nothing on a 3x10 keyboard uses: the catch-all type and thumbs.
+        let unused = format!("{all}\nLT = 1\nRT = 1\n").replace("Sfb", "Unspecified = 0\nSfb");
+        assert_eq!(
+            weights(&unused),
+            [
+                WeightsWarning::UnusedTypes(vec!["Unspecified".into()]),
+                WeightsWarning::UnusedFingers(vec![LT, RT]),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let dir = std::env::temp_dir().join(format!("oxeygen-weights-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("typo.toml");
+        std::fs::write(&path, "Sfb = -3\nInroll = \"two\"\n").unwrap();
+        assert!(matches!(
+            Weights::load(&path),
+            Err(WeightsError::ParseError {
+                line: 2,
+                column: 10,
+                ..
+            })
+        ));
+
+        let path = dir.join("finger.toml");
+        std::fs::write(&path, "Sfb = -3\n\n[fingers]\nLP = 1\nLQ = 2\n").unwrap();
+        assert!(matches!(
+            Weights::load(&path),
+            Err(WeightsError::ParseError { line: 5, .. })
+        ));
+
+        let path = dir.join("unknown.toml");
+        std::fs::write(&path, "Inrolll = 2\n\n[fingers]\n").unwrap();
+        let types = TrigramTypes::with_defaults(Keyboard::from_shape(&[10, 10, 10]));
+        assert!(matches!(
+            Weights::load(&path).unwrap().validate(&types),
+            Err(WeightsError::UnknownType { name, .. }) if name == "Inrolll"
+        ));
+
+        assert!(matches!(
+            Weights::load(dir.join("missing.toml")),
+            Err(WeightsError::ReadError { .. })
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }